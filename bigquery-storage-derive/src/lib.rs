@@ -0,0 +1,131 @@
+//! Derive macro for the `BigQueryRow` trait of [bigquery-storage](https://docs.rs/bigquery-storage).
+//! Use it through the `derive` feature of that crate rather than directly.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Data, DataStruct, DeriveInput, Error, Field, Fields, Lit, Meta, NestedMeta,
+};
+
+/// Derive `BigQueryRow` and `FromColumn` for a struct with named fields. Each field is
+/// read from the column of the same name, or from the column given by `#[bq(rename = "...")]`.
+#[proc_macro_derive(BigQueryRow, attributes(bq))]
+pub fn derive_big_query_row(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// The name of the column `field` is read from.
+fn column_name(field: &Field) -> syn::Result<String> {
+    let ident = field.ident.as_ref().expect("named fields have an ident");
+    let mut name = ident.to_string().trim_start_matches("r#").to_string();
+
+    for attr in field.attrs.iter().filter(|attr| attr.path.is_ident("bq")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            other => {
+                return Err(Error::new_spanned(
+                    other,
+                    "expected `#[bq(rename = \"...\")]`",
+                ))
+            }
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(rename)) if rename.path.is_ident("rename") => {
+                    match rename.lit {
+                        Lit::Str(lit) => name = lit.value(),
+                        other => return Err(Error::new_spanned(other, "expected a string")),
+                    }
+                }
+                other => {
+                    return Err(Error::new_spanned(
+                        other,
+                        "unknown attribute, expected `rename = \"...\"`",
+                    ))
+                }
+            }
+        }
+    }
+
+    Ok(name)
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(DataStruct {
+            fields: Fields::Named(fields),
+            ..
+        }) => &fields.named,
+        _ => {
+            return Err(Error::new_spanned(
+                ident,
+                "BigQueryRow requires a struct with named fields",
+            ))
+        }
+    };
+
+    let mut names = Vec::with_capacity(fields.len());
+    let mut idents = Vec::with_capacity(fields.len());
+    let mut types = Vec::with_capacity(fields.len());
+    for field in fields.iter() {
+        names.push(column_name(field)?);
+        idents.push(field.ident.clone());
+        types.push(field.ty.clone());
+    }
+
+    // Select each column once, even if several fields read it
+    let mut selected_names = Vec::with_capacity(names.len());
+    let mut selected_types = Vec::with_capacity(types.len());
+    for (name, ty) in names.iter().zip(types.iter()) {
+        if !selected_names.contains(name) {
+            selected_names.push(name.clone());
+            selected_types.push(ty.clone());
+        }
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::bigquery_storage::BigQueryRow for #ident #ty_generics #where_clause {
+            fn selected_fields() -> ::std::vec::Vec<::std::string::String> {
+                let mut fields = ::std::vec::Vec::new();
+                #(
+                    ::bigquery_storage::row::push_selected_fields::<#selected_types>(&mut fields, #selected_names);
+                )*
+                fields
+            }
+
+            fn from_record_batch(
+                batch: &::bigquery_storage::arrow::record_batch::RecordBatch,
+            ) -> ::std::result::Result<::std::vec::Vec<Self>, ::bigquery_storage::Error> {
+                let columns = ::bigquery_storage::row::Columns::from_record_batch(batch);
+                (0..batch.num_rows())
+                    .map(|row| {
+                        ::std::result::Result::<Self, ::bigquery_storage::Error>::Ok(Self {
+                            #( #idents: columns.get::<#types>(#names, row)?, )*
+                        })
+                    })
+                    .collect()
+            }
+        }
+
+        impl #impl_generics ::bigquery_storage::FromColumn for #ident #ty_generics #where_clause {
+            fn nested_fields() -> ::std::vec::Vec<::std::string::String> {
+                <Self as ::bigquery_storage::BigQueryRow>::selected_fields()
+            }
+
+            fn from_column(
+                array: &dyn ::bigquery_storage::arrow::array::Array,
+                row: usize,
+            ) -> ::std::result::Result<Self, ::bigquery_storage::ColumnError> {
+                let columns = ::bigquery_storage::row::Columns::from_struct(array, row)?;
+                ::std::result::Result::Ok(Self {
+                    #( #idents: columns.get::<#types>(#names, row)?, )*
+                })
+            }
+        }
+    })
+}
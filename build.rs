@@ -1,10 +1,16 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    if !std::path::Path::new("googleapis/google").is_dir() {
+        return Err("googleapis is missing, see the Building section of the README".into());
+    }
     tonic_build::configure().format(false).compile(
         &[
             "googleapis/google/cloud/bigquery/storage/v1/arrow.proto",
             "googleapis/google/cloud/bigquery/storage/v1/avro.proto",
+            "googleapis/google/cloud/bigquery/storage/v1/protobuf.proto",
             "googleapis/google/cloud/bigquery/storage/v1/storage.proto",
             "googleapis/google/cloud/bigquery/storage/v1/stream.proto",
+            "googleapis/google/cloud/bigquery/storage/v1/table.proto",
+            "googleapis/google/rpc/error_details.proto",
         ],
         &["googleapis"],
     )?;
@@ -0,0 +1,502 @@
+//! Sources of OAuth2 access tokens used to authenticate a [`Client`](crate::client::Client).
+//!
+//! Any type implementing [`TokenProvider`](TokenProvider) can be given to
+//! [`Client::new`](crate::client::Client::new). This module implements it for a
+//! yup_oauth2 [`Authenticator`](yup_oauth2::authenticator::Authenticator), a fixed
+//! [`StaticToken`](StaticToken), the [`MetadataServer`](MetadataServer) of Google Compute
+//! Engine and workload identity federation with an [`ExternalAccount`](ExternalAccount).
+use bytes::Bytes;
+use futures::future::{ready, BoxFuture, FutureExt};
+use hyper::client::connect::Connect;
+use hyper::client::HttpConnector;
+use hyper::{Body, Method, Request};
+use hyper_rustls::HttpsConnector;
+use rustls::ClientConfig;
+use serde_json::Value;
+use tokio::sync::Mutex;
+use yup_oauth2::authenticator::Authenticator;
+
+use std::future::Future;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::Error;
+
+/// Cached tokens are refreshed when they expire in less than this.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+/// Used when a token endpoint does not say when its token expires.
+const DEFAULT_LIFETIME: Duration = Duration::from_secs(3600);
+
+static METADATA_HOST: &str = "metadata.google.internal";
+static CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+static TOKEN_EXCHANGE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+static ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+/// A source of OAuth2 access tokens. Implementations are expected to cache their
+/// tokens, as one is requested for every call to the API.
+pub trait TokenProvider: Send + Sync {
+    /// Get a valid access token for `scopes`.
+    fn token<'a>(&'a self, scopes: &'a [&'a str]) -> BoxFuture<'a, Result<String, Error>>;
+}
+
+impl<C> TokenProvider for Authenticator<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    fn token<'a>(&'a self, scopes: &'a [&'a str]) -> BoxFuture<'a, Result<String, Error>> {
+        async move {
+            let token = Authenticator::token(self, scopes).await?;
+            Ok(token.as_str().to_string())
+        }
+        .boxed()
+    }
+}
+
+/// A fixed bearer token, e.g. obtained out of band or accepted by an emulator.
+/// It is never refreshed.
+#[derive(Clone)]
+pub struct StaticToken {
+    token: String,
+}
+
+impl StaticToken {
+    /// Authenticate every request with `token`.
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            token: token.into(),
+        }
+    }
+}
+
+impl TokenProvider for StaticToken {
+    fn token<'a>(&'a self, _scopes: &'a [&'a str]) -> BoxFuture<'a, Result<String, Error>> {
+        ready(Ok(self.token.clone())).boxed()
+    }
+}
+
+/// Tokens of the service account attached to the Compute Engine instance, GKE node or
+/// Cloud Run service this runs on, fetched from the [metadata server](https://cloud.google.com/compute/docs/access/create-enable-service-accounts-for-instances#applications).
+///
+/// The host of the metadata server is read from the `GCE_METADATA_HOST` environment
+/// variable if set, like other Google client libraries do.
+pub struct MetadataServer {
+    http: hyper::Client<HttpConnector>,
+    host: String,
+    service_account: String,
+    cache: TokenCache,
+}
+
+impl MetadataServer {
+    /// Use the default service account of the instance.
+    pub fn new() -> Self {
+        let host = std::env::var("GCE_METADATA_HOST").unwrap_or(METADATA_HOST.to_string());
+        Self {
+            http: hyper::Client::new(),
+            host,
+            service_account: "default".to_string(),
+            cache: TokenCache::default(),
+        }
+    }
+
+    /// Use the service account with this email instead of the default one.
+    pub fn service_account(mut self, email: impl Into<String>) -> Self {
+        self.service_account = email.into();
+        self
+    }
+
+    async fn fetch(&self, scopes: &[&str]) -> Result<(String, Duration), Error> {
+        let uri = format!(
+            "http://{}/computeMetadata/v1/instance/service-accounts/{}/token?scopes={}",
+            self.host,
+            self.service_account,
+            percent_encode(&scopes.join(","))
+        );
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(uri)
+            .header("Metadata-Flavor", "Google")
+            .body(Body::empty())?;
+        let resp = send_json(&self.http, req).await?;
+        Ok((string_field(&resp, "access_token")?, expires_in(&resp)))
+    }
+}
+
+impl Default for MetadataServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TokenProvider for MetadataServer {
+    fn token<'a>(&'a self, scopes: &'a [&'a str]) -> BoxFuture<'a, Result<String, Error>> {
+        self.cache.get_or_fetch(scopes, self.fetch(scopes)).boxed()
+    }
+}
+
+enum SubjectTokenFormat {
+    Text,
+    Json(String),
+}
+
+enum CredentialSource {
+    File(String),
+    Url(String, Vec<(String, String)>),
+}
+
+/// Credentials that could not be loaded, e.g. from an invalid configuration file.
+#[derive(Debug)]
+pub struct CredentialsError {
+    message: String,
+}
+
+impl CredentialsError {
+    pub(crate) fn new<S: Into<String>>(message: S) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for CredentialsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CredentialsError {}
+
+/// Credentials of [workload identity federation](https://cloud.google.com/iam/docs/workload-identity-federation),
+/// from an `external_account` configuration file as generated by
+/// `gcloud iam workload-identity-pools create-cred-config`.
+///
+/// The token of the external identity provider is read from a file or fetched from
+/// a URL, exchanged against a Google access token with the Security Token Service and,
+/// if configured, used to impersonate a service account. Executable and AWS credential
+/// sources are not supported.
+pub struct ExternalAccount {
+    http: HttpsClient,
+    audience: String,
+    subject_token_type: String,
+    token_url: String,
+    impersonation_url: Option<String>,
+    source: CredentialSource,
+    format: SubjectTokenFormat,
+    cache: TokenCache,
+}
+
+impl ExternalAccount {
+    /// Load the configuration file named by the `GOOGLE_APPLICATION_CREDENTIALS`
+    /// environment variable.
+    pub async fn from_env() -> Result<Self, Error> {
+        let path = std::env::var("GOOGLE_APPLICATION_CREDENTIALS")
+            .map_err(|_| Error::credentials("GOOGLE_APPLICATION_CREDENTIALS is not set"))?;
+        Self::from_file(path).await
+    }
+
+    /// Load the configuration file at `path`.
+    pub async fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let config = tokio::fs::read_to_string(path).await?;
+        Self::from_json(&config)
+    }
+
+    /// Parse the content of a configuration file. Fails with [`Error::Credentials`](crate::Error::Credentials)
+    /// if the root certificates of the platform, to reach the token endpoints, cannot be loaded.
+    pub fn from_json(config: &str) -> Result<Self, Error> {
+        let config: Value = serde_json::from_str(config)?;
+        if config["type"] != "external_account" {
+            return Err(Error::credentials(
+                "expected credentials of type external_account",
+            ));
+        }
+
+        let credential_source = &config["credential_source"];
+        let source = if let Some(file) = credential_source["file"].as_str() {
+            CredentialSource::File(file.to_string())
+        } else if let Some(url) = credential_source["url"].as_str() {
+            let headers = credential_source["headers"]
+                .as_object()
+                .map(|headers| {
+                    headers
+                        .iter()
+                        .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+                        .collect()
+                })
+                .unwrap_or_default();
+            CredentialSource::Url(url.to_string(), headers)
+        } else {
+            return Err(Error::credentials(
+                "unsupported credential source, expected a file or a url",
+            ));
+        };
+
+        let format = &credential_source["format"];
+        let format = match format["type"].as_str() {
+            Some("json") => {
+                let field = format["subject_token_field_name"]
+                    .as_str()
+                    .ok_or_else(|| Error::credentials("missing subject_token_field_name"))?;
+                SubjectTokenFormat::Json(field.to_string())
+            }
+            Some("text") | None => SubjectTokenFormat::Text,
+            Some(other) => {
+                return Err(Error::credentials(format!(
+                    "unsupported subject token format {}",
+                    other
+                )))
+            }
+        };
+
+        let config_field = |field: &str| {
+            config[field]
+                .as_str()
+                .map(|value| value.to_string())
+                .ok_or_else(|| Error::credentials(format!("missing {}", field)))
+        };
+
+        Ok(Self {
+            http: https_client()?,
+            audience: config_field("audience")?,
+            subject_token_type: config_field("subject_token_type")?,
+            token_url: config_field("token_url")?,
+            impersonation_url: config_field("service_account_impersonation_url").ok(),
+            source,
+            format,
+            cache: TokenCache::default(),
+        })
+    }
+
+    async fn subject_token(&self) -> Result<String, Error> {
+        let raw = match &self.source {
+            // Read on every refresh, as the file may be rotated
+            CredentialSource::File(path) => tokio::fs::read_to_string(path).await?,
+            CredentialSource::Url(url, headers) => {
+                let mut req = Request::builder().method(Method::GET).uri(url);
+                for (name, value) in headers {
+                    req = req.header(name.as_str(), value.as_str());
+                }
+                let body = send(&self.http, req.body(Body::empty())?).await?;
+                String::from_utf8_lossy(&body).into_owned()
+            }
+        };
+        match &self.format {
+            SubjectTokenFormat::Text => Ok(raw.trim().to_string()),
+            SubjectTokenFormat::Json(field) => {
+                let value: Value = serde_json::from_str(&raw)?;
+                string_field(&value, field)
+            }
+        }
+    }
+
+    async fn fetch(&self, scopes: &[&str]) -> Result<(String, Duration), Error> {
+        let subject_token = self.subject_token().await?;
+
+        // The federated token is only used to impersonate the service account, which
+        // requires the cloud-platform scope.
+        let sts_scope = match self.impersonation_url {
+            Some(_) => CLOUD_PLATFORM_SCOPE.to_string(),
+            None => scopes.join(" "),
+        };
+        let form = [
+            ("grant_type", TOKEN_EXCHANGE_GRANT_TYPE),
+            ("audience", self.audience.as_str()),
+            ("scope", sts_scope.as_str()),
+            ("requested_token_type", ACCESS_TOKEN_TYPE),
+            ("subject_token", subject_token.as_str()),
+            ("subject_token_type", self.subject_token_type.as_str()),
+        ];
+        let body = form
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, percent_encode(v)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(&self.token_url)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(Body::from(body))?;
+        let resp = send_json(&self.http, req).await?;
+        let federated_token = string_field(&resp, "access_token")?;
+
+        let impersonation_url = match &self.impersonation_url {
+            Some(url) => url,
+            None => return Ok((federated_token, expires_in(&resp))),
+        };
+
+        let body = serde_json::json!({
+            "scope": scopes,
+            "lifetime": format!("{}s", DEFAULT_LIFETIME.as_secs()),
+        });
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(impersonation_url)
+            .header("Authorization", format!("Bearer {}", federated_token))
+            .header("Content-Type", "application/json")
+            .body(Body::from(body.to_string()))?;
+        let resp = send_json(&self.http, req).await?;
+        Ok((string_field(&resp, "accessToken")?, DEFAULT_LIFETIME))
+    }
+}
+
+impl TokenProvider for ExternalAccount {
+    fn token<'a>(&'a self, scopes: &'a [&'a str]) -> BoxFuture<'a, Result<String, Error>> {
+        self.cache.get_or_fetch(scopes, self.fetch(scopes)).boxed()
+    }
+}
+
+type HttpsClient = hyper::Client<HttpsConnector<HttpConnector>>;
+
+/// A client trusting the root certificates of the platform, which fails instead of
+/// panicking like [`HttpsConnector::with_native_roots`] when there are none.
+fn https_client() -> Result<HttpsClient, Error> {
+    let mut config = ClientConfig::new();
+    config.root_store = match rustls_native_certs::load_native_certs() {
+        Ok(store) | Err((Some(store), _)) => store,
+        Err((None, err)) => {
+            return Err(Error::credentials(format!(
+                "cannot load the root certificates: {}",
+                err
+            )))
+        }
+    };
+    if config.root_store.is_empty() {
+        return Err(Error::credentials("no root certificates found"));
+    }
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+    let mut http = HttpConnector::new();
+    http.enforce_http(false);
+    Ok(hyper::Client::builder().build(HttpsConnector::from((http, config))))
+}
+
+struct CachedToken {
+    scopes: Vec<String>,
+    token: String,
+    expires_at: Instant,
+}
+
+/// The last token fetched by a provider. Concurrent requests wait for the same
+/// refresh instead of each fetching a token.
+#[derive(Default)]
+struct TokenCache {
+    cached: Mutex<Option<CachedToken>>,
+}
+
+impl TokenCache {
+    async fn get_or_fetch<F>(&self, scopes: &[&str], fetch: F) -> Result<String, Error>
+    where
+        F: Future<Output = Result<(String, Duration), Error>>,
+    {
+        let mut cached = self.cached.lock().await;
+        if let Some(cached) = cached.as_ref() {
+            if cached.scopes == scopes && cached.expires_at > Instant::now() + EXPIRY_MARGIN {
+                return Ok(cached.token.clone());
+            }
+        }
+
+        let (token, lifetime) = fetch.await?;
+        *cached = Some(CachedToken {
+            scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+            token: token.clone(),
+            expires_at: Instant::now() + lifetime,
+        });
+        Ok(token)
+    }
+}
+
+async fn send<C>(http: &hyper::Client<C>, req: Request<Body>) -> Result<Bytes, Error>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    let resp = http.request(req).await?;
+    let status = resp.status();
+    let body = hyper::body::to_bytes(resp.into_body()).await?;
+    if !status.is_success() {
+        return Err(Error::invalid(format!(
+            "token request failed with {}: {}",
+            status,
+            String::from_utf8_lossy(&body)
+        )));
+    }
+    Ok(body)
+}
+
+async fn send_json<C>(http: &hyper::Client<C>, req: Request<Body>) -> Result<Value, Error>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    let body = send(http, req).await?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+fn string_field(value: &Value, field: &str) -> Result<String, Error> {
+    value[field]
+        .as_str()
+        .map(|s| s.to_string())
+        .ok_or_else(|| Error::invalid(format!("missing {} in token response", field)))
+}
+
+fn expires_in(value: &Value) -> Duration {
+    value["expires_in"]
+        .as_u64()
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_LIFETIME)
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_external_account_config() {
+        let config = r#"{
+            "type": "external_account",
+            "audience": "//iam.googleapis.com/projects/1/locations/global/workloadIdentityPools/pool/providers/provider",
+            "subject_token_type": "urn:ietf:params:oauth:token-type:jwt",
+            "token_url": "https://sts.googleapis.com/v1/token",
+            "service_account_impersonation_url": "https://iamcredentials.googleapis.com/v1/projects/-/serviceAccounts/sa@project.iam.gserviceaccount.com:generateAccessToken",
+            "credential_source": {
+                "url": "http://localhost:5000/token",
+                "headers": { "Metadata": "True" },
+                "format": { "type": "json", "subject_token_field_name": "access_token" }
+            }
+        }"#;
+        let account = ExternalAccount::from_json(config).unwrap();
+        assert_eq!(account.token_url, "https://sts.googleapis.com/v1/token");
+        assert!(account.impersonation_url.is_some());
+        match &account.source {
+            CredentialSource::Url(url, headers) => {
+                assert_eq!(url, "http://localhost:5000/token");
+                assert_eq!(headers, &vec![("Metadata".to_string(), "True".to_string())]);
+            }
+            CredentialSource::File(_) => panic!("expected a url credential source"),
+        }
+        match &account.format {
+            SubjectTokenFormat::Json(field) => assert_eq!(field, "access_token"),
+            SubjectTokenFormat::Text => panic!("expected a json subject token"),
+        }
+
+        let service_account = r#"{ "type": "service_account" }"#;
+        assert!(ExternalAccount::from_json(service_account).is_err());
+    }
+
+    #[test]
+    fn percent_encode_form_values() {
+        assert_eq!(
+            percent_encode("https://www.googleapis.com/auth/bigquery a~b"),
+            "https%3A%2F%2Fwww.googleapis.com%2Fauth%2Fbigquery%20a~b"
+        );
+    }
+}
@@ -1,7 +1,7 @@
 //! The main module of this crate.
 //! # Example
-//! To build a [`Client`](Client) you just need an [`Authenticator`](yup_oauth2::authenticator::Authenticator). For example, if you want to use a service account:
-//! ```rust
+//! To build a [`Client`](Client) you just need a [`TokenProvider`](crate::auth::TokenProvider), such as an [`Authenticator`](yup_oauth2::authenticator::Authenticator). For example, if you want to use a service account:
+//! ```rust,no_run
 //! #[tokio::main(flavor = "current_thread")]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
 //!     // 1. Load the desired secret (here, a service account key)
@@ -14,48 +14,225 @@
 //!         .await?;
 //!
 //!     // 3. Create a Client
-//!     let mut client = bigquery_storage::Client::new(auth).await?;
+//!     let client = bigquery_storage::Client::new(auth).await?;
 //!
 //!     Ok(())
 //! }
 //! ```
-use hyper::client::connect::Connect;
-use yup_oauth2::authenticator::Authenticator;
+use futures::channel::mpsc;
+use futures::future::FutureExt;
+use futures::stream::{self, StreamExt};
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
 use prost_types::Timestamp;
 use tonic::metadata::MetadataValue;
-use tonic::transport::{Channel, ClientTlsConfig};
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint};
 use tonic::{Request, Streaming};
 
+use crate::error::is_session_expired;
 use crate::googleapis::big_query_read_client::BigQueryReadClient;
+use crate::googleapis::big_query_write_client::BigQueryWriteClient;
 use crate::googleapis::{
     read_session::{TableModifiers, TableReadOptions},
-    CreateReadSessionRequest, DataFormat, ReadRowsRequest, ReadRowsResponse,
-    ReadSession as BigQueryReadSession, ReadStream,
+    write_stream::Type as WriteStreamType,
+    AppendRowsRequest, BatchCommitWriteStreamsRequest, BatchCommitWriteStreamsResponse,
+    CreateReadSessionRequest, CreateWriteStreamRequest, DataFormat, FinalizeWriteStreamRequest,
+    FinalizeWriteStreamResponse, FlushRowsRequest, FlushRowsResponse, ProtoSchema, ReadRowsRequest,
+    ReadRowsResponse, ReadSession as BigQueryReadSession, ReadStream, SplitReadStreamRequest,
+    SplitReadStreamResponse, WriteStream,
 };
+use crate::handle::{system_time_to_timestamp, timestamp_to_system_time};
+use crate::metrics::{observe, Call, Metrics};
+use crate::read::{Handover, Open, Reconnect, SessionClaim, Split};
+use crate::restriction::Expr;
+use crate::timeout::{set_deadline, CallLimits};
+use crate::trace::span;
+use crate::write::AppendRowsConnection;
+use crate::CancellationToken;
 use crate::Error;
+use crate::ParallelReader;
+use crate::RetryPolicy;
 use crate::RowsStreamReader;
+use crate::TokenProvider;
+use crate::{AppendedRows, StreamWriter, WriteSession};
+use crate::{ReadSessionHandle, ReadStreamHandle};
+use crate::{SerializedSchema, SessionSchema};
 
-static API_ENDPOINT: &'static str = "https://bigquerystorage.googleapis.com";
-static API_DOMAIN: &'static str = "bigquerystorage.googleapis.com";
-static API_SCOPE: &'static str = "https://www.googleapis.com/auth/bigquery";
+#[cfg(feature = "arrow")]
+use crate::write::proto_schema_from_arrow;
+#[cfg(feature = "arrow")]
+use crate::{BigQueryRow, RowStream};
+#[cfg(feature = "arrow")]
+use arrow::record_batch::RecordBatch;
+
+static API_ENDPOINT: &str = "https://bigquerystorage.googleapis.com";
+static API_SCOPE: &str = "https://www.googleapis.com/auth/bigquery";
+
+#[cfg(any(feature = "arrow", not(feature = "avro")))]
+const DEFAULT_DATA_FORMAT: DataFormat = DataFormat::Arrow;
+#[cfg(all(feature = "avro", not(feature = "arrow")))]
+const DEFAULT_DATA_FORMAT: DataFormat = DataFormat::Avro;
+
+/// A margin for [`ReadSessionBuilder::as_of_now`](ReadSessionBuilder::as_of_now), so that
+/// a local clock running ahead of the server's does not request a snapshot in the future,
+/// which the API rejects. Also the margin of sessions built with
+/// [`auto_refresh`](ReadSessionBuilder::auto_refresh) without a snapshot time.
+pub const SNAPSHOT_MARGIN: Duration = Duration::from_secs(10);
+
+/// A time reported by the API, unknown if it cannot be represented on this platform.
+fn system_time(timestamp: &Timestamp) -> Option<SystemTime> {
+    timestamp_to_system_time(timestamp).ok()
+}
 
 /// A fully qualified BigQuery table. This requires a `project_id`, a `dataset_id`
-/// and a `table_id`. Only alphanumerical and underscores are allowed for `dataset_id`
-/// and `table_id`.
+/// and a `table_id`, and optionally the partition to read or write.
+///
+/// A table can be parsed from any of the usual forms:
+/// ```rust
+/// use bigquery_storage::Table;
+///
+/// let table: Table = "bigquery-public-data.london_bicycles.cycle_stations".parse()?;
+/// assert_eq!(table, "bigquery-public-data:london_bicycles.cycle_stations".parse()?);
+/// assert_eq!(
+///     table,
+///     "projects/bigquery-public-data/datasets/london_bicycles/tables/cycle_stations".parse()?
+/// );
+///
+/// let partition: Table = "my-project.my_dataset.events$20240101".parse()?;
+/// assert_eq!(partition.table_id(), "events");
+/// assert_eq!(partition.partition(), Some("20240101"));
+/// # Ok::<(), bigquery_storage::ParseTableError>(())
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Table {
     project_id: String,
     dataset_id: String,
     table_id: String,
+    partition: Option<String>,
 }
 
 impl Table {
+    /// Create a table from its components, without validating them. See
+    /// [`Table::try_new`](Table::try_new) to validate them.
     pub fn new(project_id: &str, dataset_id: &str, table_id: &str) -> Self {
         Self {
             project_id: project_id.to_string(),
             dataset_id: dataset_id.to_string(),
             table_id: table_id.to_string(),
+            partition: None,
+        }
+    }
+
+    /// Create a table from its components, checking that:
+    /// - `project_id` is made of lowercase ASCII letters, digits and hyphens, optionally
+    ///   prefixed by a domain such as `example.com:`,
+    /// - `dataset_id` is made of ASCII letters, digits and underscores,
+    /// - `table_id` is made of letters, digits, underscores, hyphens and spaces, optionally
+    ///   followed by a partition decorator such as `$20240101`.
+    pub fn try_new(
+        project_id: &str,
+        dataset_id: &str,
+        table_id: &str,
+    ) -> Result<Self, ParseTableError> {
+        let (table_id, partition) = match table_id.split_once('$') {
+            Some((table_id, partition)) => (table_id, Some(partition)),
+            None => (table_id, None),
+        };
+
+        let error = |message: &str| {
+            Err(ParseTableError::new(
+                format!("{}.{}.{}", project_id, dataset_id, table_id),
+                message,
+            ))
+        };
+
+        let valid_project = |project: &str| {
+            project.starts_with(|c: char| c.is_ascii_lowercase())
+                && !project.ends_with('-')
+                && project
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        };
+        let project = match project_id.rsplit_once(':') {
+            Some((domain, project)) => {
+                let valid_domain = !domain.is_empty()
+                    && domain.chars().all(|c| {
+                        c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '.'
+                    });
+                if !valid_domain {
+                    return error("invalid domain in the project id");
+                }
+                project
+            }
+            None => project_id,
+        };
+        if !valid_project(project) {
+            return error(
+                "the project id should start with a lowercase letter, followed by lowercase \
+                 letters, digits or hyphens",
+            );
         }
+
+        if dataset_id.is_empty()
+            || dataset_id.len() > 1024
+            || !dataset_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return error("the dataset id should be made of letters, digits or underscores");
+        }
+
+        if table_id.is_empty()
+            || table_id.len() > 1024
+            || !table_id
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == ' ')
+        {
+            return error(
+                "the table id should be made of letters, digits, underscores, hyphens or spaces",
+            );
+        }
+
+        if let Some(partition) = partition {
+            let valid_partition = partition == "__NULL__"
+                || partition == "__UNPARTITIONED__"
+                || (!partition.is_empty() && partition.chars().all(|c| c.is_ascii_digit()));
+            if !valid_partition {
+                return error(
+                    "the partition decorator should be a date such as `$20240101`, an integer, \
+                     `$__NULL__` or `$__UNPARTITIONED__`",
+                );
+            }
+        }
+
+        Ok(Self {
+            project_id: project_id.to_string(),
+            dataset_id: dataset_id.to_string(),
+            table_id: table_id.to_string(),
+            partition: partition.map(str::to_string),
+        })
+    }
+
+    /// The id of the project owning the table.
+    pub fn project_id(&self) -> &str {
+        &self.project_id
+    }
+
+    /// The id of the dataset of the table.
+    pub fn dataset_id(&self) -> &str {
+        &self.dataset_id
+    }
+
+    /// The id of the table, without the partition decorator.
+    pub fn table_id(&self) -> &str {
+        &self.table_id
+    }
+
+    /// The partition decorator of the table, without the `$`, e.g. `20240101`.
+    pub fn partition(&self) -> Option<&str> {
+        self.partition.as_deref()
     }
 }
 
@@ -65,7 +242,128 @@ impl std::fmt::Display for Table {
             f,
             "projects/{}/datasets/{}/tables/{}",
             self.project_id, self.dataset_id, self.table_id
-        )
+        )?;
+        if let Some(partition) = &self.partition {
+            write!(f, "${}", partition)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Table {
+    type Err = ParseTableError;
+
+    /// Parse `project.dataset.table`, `project:dataset.table` or
+    /// `projects/project/datasets/dataset/tables/table`, optionally between backticks.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let trimmed = trimmed
+            .strip_prefix('`')
+            .and_then(|unquoted| unquoted.strip_suffix('`'))
+            .unwrap_or(trimmed);
+        let invalid = || ParseTableError::new(s.to_string(), "unrecognized table format");
+
+        if let Some(path) = trimmed.strip_prefix("projects/") {
+            let parts: Vec<&str> = path.split('/').collect();
+            return match parts.as_slice() {
+                [project_id, "datasets", dataset_id, "tables", table_id] => {
+                    Self::try_new(project_id, dataset_id, table_id)
+                }
+                _ => Err(invalid()),
+            };
+        }
+
+        // Domain-scoped projects are written `example.com:project`, so the project id
+        // may contain a colon, and dots before it
+        let (domain, rest) = match trimmed.rsplit_once(':') {
+            Some((domain, rest)) => (Some(domain), rest),
+            None => (None, trimmed),
+        };
+        let parts: Vec<&str> = rest.split('.').collect();
+        match (domain, parts.as_slice()) {
+            (Some(project_id), [dataset_id, table_id]) => {
+                Self::try_new(project_id, dataset_id, table_id)
+            }
+            (Some(domain), [project_id, dataset_id, table_id]) => {
+                Self::try_new(&format!("{}:{}", domain, project_id), dataset_id, table_id)
+            }
+            (None, [project_id, dataset_id, table_id]) => {
+                Self::try_new(project_id, dataset_id, table_id)
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl TryFrom<&str> for Table {
+    type Error = ParseTableError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl TryFrom<String> for Table {
+    type Error = ParseTableError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// A table identifier that could not be parsed or is invalid.
+#[derive(Debug)]
+pub struct ParseTableError {
+    input: String,
+    message: String,
+}
+
+impl ParseTableError {
+    fn new<S: Into<String>>(input: String, message: S) -> Self {
+        Self {
+            input,
+            message: message.into(),
+        }
+    }
+
+    /// The table identifier that was rejected.
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+}
+
+impl std::fmt::Display for ParseTableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid table `{}`: {}", self.input, self.message)
+    }
+}
+
+impl std::error::Error for ParseTableError {}
+
+/// The snapshot time of a read session, see [`ReadSessionBuilder::snapshot_time`](ReadSessionBuilder::snapshot_time).
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapshotTime(Timestamp);
+
+impl From<Timestamp> for SnapshotTime {
+    fn from(timestamp: Timestamp) -> Self {
+        Self(timestamp)
+    }
+}
+
+impl From<SystemTime> for SnapshotTime {
+    fn from(time: SystemTime) -> Self {
+        Self(system_time_to_timestamp(time))
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> From<chrono::DateTime<Tz>> for SnapshotTime {
+    fn from(time: chrono::DateTime<Tz>) -> Self {
+        Self(Timestamp {
+            seconds: time.timestamp(),
+            // Leap seconds are reported as nanoseconds past 1e9
+            nanos: time.timestamp_subsec_nanos().min(999_999_999) as i32,
+        })
     }
 }
 
@@ -85,16 +383,17 @@ macro_rules! read_session_builder {
 
         /// A builder for [`ReadSession`](crate::client::ReadSession).
         /// When in doubt about what a field does, please refer to [`CreateReadSessionRequest`](crate::googleapis::CreateReadSessionRequest) and the [official API](https://cloud.google.com/bigquery/docs/reference/storage/rpc/google.cloud.bigquery.storage.v1) documentation.
-        pub struct ReadSessionBuilder<'a, T> {
-            client: &'a mut Client<T>,
+        pub struct ReadSessionBuilder {
+            client: Client,
             table: Table,
+            snapshot_time: Option<Timestamp>,
             opts: ReadSessionBuilderOpts
         }
 
-        impl<'a, T> ReadSessionBuilder<'a, T> {
-            fn new(client: &'a mut Client<T>, table: Table) -> Self {
+        impl ReadSessionBuilder {
+            fn new(client: Client, table: Table) -> Self {
                 let opts = ReadSessionBuilderOpts::default();
-                Self { client, table, opts }
+                Self { client, table, snapshot_time: None, opts }
             }
 
             $(
@@ -109,10 +408,8 @@ macro_rules! read_session_builder {
 }
 
 read_session_builder! {
-    #[doc = "Sets the data format of the output data. Defaults to Arrow if not set."]
+    #[doc = "Sets the data format of the output data. Defaults to Arrow if not set, or to Avro if only the `avro` feature is enabled."]
     data_format: DataFormat,
-    #[doc = "Sets the snapshot time of the table. If not set, interpreted as now."]
-    snapshot_time: Timestamp,
     #[doc = "Names of the fields in the table that should be read. If empty or not set, all fields will be read. If the specified field is a nested field, all the sub-fields in the field will be selected. The output field order is unrelated to the order of fields in selected_fields."]
     selected_fields: Vec<String>,
     #[doc = "SQL text filtering statement, similar to a `WHERE` clause in a query. Aggregates are not supported.\n"]
@@ -121,21 +418,82 @@ read_session_builder! {
 - `date_field = CAST('2014-9-27' as DATE)` \n
 - `nullable_field is not NULL` \n
 - `st_equals(geo_field, st_geofromtext(\"POINT(2, 2)\"))` \n
-- `numeric_field BETWEEN 1.0 AND 5.0`"]
+- `numeric_field BETWEEN 1.0 AND 5.0` \n
+See also [`filter`](ReadSessionBuilder::filter), to build it without formatting SQL by hand."]
     row_restriction: String,
     #[doc = "Max initial number of streams. If unset or zero, the server will provide a value of streams so as to produce reasonable throughput. Must be non-negative. The number of streams may be lower than the requested number, depending on the amount parallelism that is reasonable for the table. Error will be returned if the max count is greater than the current system max limit of 1,000."]
     max_stream_count: i32,
     #[doc = "The request project that owns the session. If not set, defaults to the project owning the table to be read."]
     parent_project_id: String,
+    #[doc = "Recreate the session with the same options once it expired, and hand the work left over to the new session: the streams not taken yet, and those opened but not read from. The streams of the new session split the table differently, so once rows of the expired session were received, they could not be told apart in the new one: [`Error::SessionExpired`](crate::Error::SessionExpired) is returned instead.\n\nThe snapshot time is pinned, to the time the session is built (minus [`SNAPSHOT_MARGIN`](SNAPSHOT_MARGIN), like [`as_of_now`](ReadSessionBuilder::as_of_now)) if it is not set, so that the new session reads the same data."]
+    auto_refresh: bool,
 }
 
-impl<'a, C> ReadSessionBuilder<'a, C>
-where
-    C: Connect + Clone + Send + Sync + 'static,
-{
+impl ReadSessionBuilder {
+    /// Sets the [`row_restriction`](ReadSessionBuilder::row_restriction) from an
+    /// expression of the [`restriction`](crate::restriction) module.
+    /// ```rust
+    /// # async fn f(client: bigquery_storage::Client, table: bigquery_storage::Table) {
+    /// use bigquery_storage::col;
+    ///
+    /// let builder = client
+    ///     .read_session_builder(table)
+    ///     .filter(col("int_field").gt(5).and(col("nullable_field").is_not_null()));
+    /// # }
+    /// ```
+    pub fn filter(self, filter: Expr) -> Self {
+        self.row_restriction(filter.into())
+    }
+
+    /// Sets the snapshot time of the table, from a [`SystemTime`](std::time::SystemTime),
+    /// a protobuf [`Timestamp`](prost_types::Timestamp) or, with the `chrono` feature, a
+    /// `chrono::DateTime`. If not set, interpreted as now.
+    pub fn snapshot_time<T: Into<SnapshotTime>>(mut self, snapshot_time: T) -> Self {
+        self.snapshot_time = Some(snapshot_time.into().0);
+        self
+    }
+
+    /// Sets the snapshot time to the current time of the local clock minus `margin`, so
+    /// that its [`snapshot_time`](ReadSession::snapshot_time) is known, to create further
+    /// sessions at the same snapshot with [`same_snapshot_as`](ReadSessionBuilder::same_snapshot_as).
+    ///
+    /// The margin absorbs the skew between the local clock and the server's, as the API
+    /// rejects snapshots in the future: [`SNAPSHOT_MARGIN`](SNAPSHOT_MARGIN) suits clocks
+    /// synchronized with NTP. Rows committed during the margin are not read.
+    pub fn as_of_now(self, margin: Duration) -> Self {
+        self.snapshot_time(SystemTime::now() - margin)
+    }
+
+    /// Read the table at the same snapshot as `session`, e.g. to read several tables
+    /// consistently. Does nothing if the snapshot of `session` is not known, see
+    /// [`ReadSession::snapshot_time`](ReadSession::snapshot_time).
+    /// ```rust
+    /// # async fn f(client: bigquery_storage::Client) -> Result<(), bigquery_storage::Error> {
+    /// use bigquery_storage::{Table, SNAPSHOT_MARGIN};
+    ///
+    /// let orders = client
+    ///     .read_session_builder(Table::new("project", "shop", "orders"))
+    ///     .as_of_now(SNAPSHOT_MARGIN)
+    ///     .build()
+    ///     .await?;
+    /// let customers = client
+    ///     .read_session_builder(Table::new("project", "shop", "customers"))
+    ///     .same_snapshot_as(&orders)
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn same_snapshot_as(mut self, session: &ReadSession) -> Self {
+        if let Some(snapshot_time) = session.snapshot_timestamp() {
+            self.snapshot_time = Some(snapshot_time);
+        }
+        self
+    }
+
     /// Build the [`ReadSession`](ReadSession). This will hit Google's API and
     /// prepare the desired read streams.
-    pub async fn build(self) -> Result<ReadSession<'a, C>, Error> {
+    pub async fn build(mut self) -> Result<ReadSession, Error> {
         let table = self.table.to_string();
 
         let mut inner = BigQueryReadSession {
@@ -143,10 +501,16 @@ where
             ..Default::default()
         };
 
-        let data_format = self.opts.data_format.unwrap_or(DataFormat::Arrow);
+        let data_format = self.opts.data_format.unwrap_or(DEFAULT_DATA_FORMAT);
         inner.set_data_format(data_format);
 
-        if let Some(snapshot_time) = self.opts.snapshot_time {
+        let auto_refresh = self.opts.auto_refresh.unwrap_or_default();
+        if auto_refresh && self.snapshot_time.is_none() {
+            let snapshot_time = SystemTime::now() - SNAPSHOT_MARGIN;
+            self.snapshot_time = Some(system_time_to_timestamp(snapshot_time));
+        }
+
+        if let Some(snapshot_time) = self.snapshot_time {
             inner.table_modifiers = Some(TableModifiers {
                 snapshot_time: Some(snapshot_time),
             });
@@ -160,6 +524,7 @@ where
         if let Some(row_restriction) = self.opts.row_restriction {
             tro.row_restriction = row_restriction;
         }
+        inner.read_options = Some(tro);
 
         let parent_project_id = self.opts.parent_project_id.unwrap_or(self.table.project_id);
         let parent = format!("projects/{}", parent_project_id);
@@ -171,149 +536,1829 @@ where
             max_stream_count,
         };
 
+        let refresh = if auto_refresh {
+            Some(req.clone())
+        } else {
+            None
+        };
         let inner = self.client.create_read_session(req).await?;
 
-        Ok(ReadSession {
-            client: self.client,
-            inner,
-        })
+        let mut read_session = ReadSession::new(self.client, inner);
+        if let Some(req) = refresh {
+            read_session.refresh = Some(Arc::new(Refresh {
+                req,
+                lock: tokio::sync::Mutex::new(()),
+            }));
+        }
+        Ok(read_session)
+    }
+
+    /// Build the [`ReadSession`](ReadSession) and read all its streams concurrently,
+    /// decoding each row into a `T`, usually with `#[derive(BigQueryRow)]`. Only the
+    /// fields of `T` are selected, and the data format is set to Arrow.
+    #[cfg(feature = "arrow")]
+    pub async fn read_as<T>(self) -> Result<RowStream<T>, Error>
+    where
+        T: BigQueryRow + Send + 'static,
+    {
+        let read_session = self
+            .selected_fields(T::selected_fields())
+            .data_format(DataFormat::Arrow)
+            .build()
+            .await?;
+
+        let rows = read_session
+            .parallel_reader()
+            .into_record_batch_stream()
+            .map(|batch| {
+                let rows = batch.and_then(|batch| T::from_record_batch(&batch));
+                match rows {
+                    Ok(rows) => stream::iter(rows.into_iter().map(Ok)).left_stream(),
+                    Err(e) => stream::once(async { Err(e) }).right_stream(),
+                }
+            })
+            .flatten()
+            .boxed();
+        Ok(rows)
     }
 }
 
 /// A practical wrapper around a [BigQuery Storage read session](https://cloud.google.com/bigquery/docs/reference/storage#create_a_session).
 /// Do not create it manually, use [`Client::read_session_builder`](Client::read_session_builder) instead.
-pub struct ReadSession<'a, C> {
-    client: &'a mut Client<C>,
-    inner: BigQueryReadSession,
+///
+/// A `ReadSession` owns a handle to its [`Client`](Client), so it can be moved into
+/// spawned tasks. Clones share the same queue of streams: each stream is only ever
+/// handed out once, whichever clone takes it.
+///
+/// Sessions expire after about 6 hours, see [`expire_time`](ReadSession::expire_time).
+/// Their streams then fail to open with [`Error::SessionExpired`](crate::Error::SessionExpired),
+/// unless the session was built with [`auto_refresh`](ReadSessionBuilder::auto_refresh).
+pub struct ReadSession {
+    client: Client,
+    state: Arc<Mutex<SessionState>>,
+    refresh: Option<Arc<Refresh>>,
 }
 
-impl<'a, C> ReadSession<'a, C>
-where
-    C: Connect + Clone + Send + Sync + 'static,
-{
-    /// Take the next stream in this read session. Returns `None` when all streams have been taken.
-    pub async fn next_stream(&mut self) -> Result<Option<RowsStreamReader>, Error> {
-        match self.inner.streams.pop() {
-            Some(ReadStream { name }) => {
-                let rows_stream = self.client.read_stream_rows(&name).await?;
-                let schema = self
-                    .inner
-                    .schema
-                    .clone()
-                    .ok_or(Error::invalid("empty schema response"))?;
-                Ok(Some(RowsStreamReader::new(schema, rows_stream)))
-            }
-            None => Ok(None),
+/// The state of a read session, shared by its clones and replaced when it is refreshed.
+struct SessionState {
+    inner: Arc<BigQueryReadSession>,
+    streams: Vec<ReadStream>,
+    /// Shared with the readers of the streams of the session.
+    claim: Arc<Mutex<SessionClaim>>,
+}
+
+impl SessionState {
+    fn new(mut inner: BigQueryReadSession) -> Self {
+        let streams = std::mem::take(&mut inner.streams);
+        Self {
+            inner: Arc::new(inner),
+            streams,
+            claim: Default::default(),
         }
     }
 }
 
-/// The main object of this crate.
-pub struct Client<C> {
-    auth: Authenticator<C>,
-    big_query_read_client: BigQueryReadClient<Channel>,
+/// How to recreate a read session.
+struct Refresh {
+    req: CreateReadSessionRequest,
+    /// Held while the session is recreated, so that it is only recreated once.
+    lock: tokio::sync::Mutex<()>,
 }
 
-impl<C> Client<C>
-where
-    C: Connect + Clone + Send + Sync + 'static,
-{
-    /// Create a new client using `auth` as a token generator.
-    pub async fn new(auth: Authenticator<C>) -> Result<Self, Error> {
-        let tls_config = ClientTlsConfig::new().domain_name(API_DOMAIN);
-        let channel = Channel::from_static(API_ENDPOINT)
-            .tls_config(tls_config)?
-            .connect()
-            .await?;
-        let big_query_read_client = BigQueryReadClient::new(channel);
-        Ok(Self {
-            auth,
-            big_query_read_client,
-        })
+/// The error of reading a stream of an expired [`ReadSession`](ReadSession).
+#[derive(Debug)]
+pub struct SessionExpired {
+    session: String,
+    expire_time: Option<SystemTime>,
+}
+
+impl SessionExpired {
+    fn new(inner: &BigQueryReadSession) -> Self {
+        Self {
+            session: inner.name.clone(),
+            expire_time: inner.expire_time.as_ref().and_then(system_time),
+        }
     }
 
-    /// Create a new [`ReadSessionBuilder`](ReadSessionBuilder).
-    pub fn read_session_builder(&mut self, table: Table) -> ReadSessionBuilder<'_, C> {
-        ReadSessionBuilder::new(self, table)
+    /// The name of the expired session.
+    pub fn session(&self) -> &str {
+        &self.session
     }
-    async fn new_request<D>(&self, t: D, params: &str) -> Result<Request<D>, Error> {
-        let token = self.auth.token(&[API_SCOPE]).await?;
-        let bearer_token = format!("Bearer {}", token.as_str());
-        let bearer_value = MetadataValue::from_str(&bearer_token)?;
-        let mut req = Request::new(t);
-        let meta = req.metadata_mut();
-        meta.insert("authorization", bearer_value);
-        meta.insert("x-goog-request-params", MetadataValue::from_str(params)?);
-        Ok(req)
+
+    /// When the session expired, as announced when it was created.
+    pub fn expire_time(&self) -> Option<SystemTime> {
+        self.expire_time
     }
-    async fn create_read_session(
-        &mut self,
-        req: CreateReadSessionRequest,
-    ) -> Result<BigQueryReadSession, Error> {
-        let table_uri = &req.read_session.as_ref().unwrap().table;
-        let params = format!("read_session.table={}", table_uri);
-        let wrapped = self.new_request(req, &params).await?;
+}
 
-        let read_session = self
-            .big_query_read_client
-            .create_read_session(wrapped)
-            .await?
-            .into_inner();
-        Ok(read_session)
+impl std::fmt::Display for SessionExpired {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "read session {} has expired", self.session)?;
+        if let Some(expire_time) = self.expire_time {
+            write!(f, " at {:?}", expire_time)?;
+        }
+        Ok(())
     }
-    async fn read_stream_rows(
-        &mut self,
-        stream: &str,
-    ) -> Result<Streaming<ReadRowsResponse>, Error> {
-        let req = ReadRowsRequest {
-            read_stream: stream.to_string(),
-            offset: 0, // TODO
-        };
-        let params = format!("read_stream={}", req.read_stream);
-        let wrapped = self.new_request(req, &params).await?;
-        let read_rows_response = self
-            .big_query_read_client
-            .read_rows(wrapped)
-            .await?
-            .into_inner();
-        Ok(read_rows_response)
+}
+
+impl std::error::Error for SessionExpired {}
+
+impl Clone for ReadSession {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            state: self.state.clone(),
+            refresh: self.refresh.clone(),
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl ReadSession {
+    fn new(client: Client, inner: BigQueryReadSession) -> Self {
+        Self {
+            client,
+            state: Arc::new(Mutex::new(SessionState::new(inner))),
+            refresh: None,
+        }
+    }
 
-    #[tokio::test]
-    async fn read_a_table_with_arrow() {
-        let sa_key = yup_oauth2::read_service_account_key("clientsecret.json")
-            .await
-            .unwrap();
-        let auth = yup_oauth2::ServiceAccountAuthenticator::builder(sa_key)
-            .build()
-            .await
-            .unwrap();
+    fn inner(&self) -> Arc<BigQueryReadSession> {
+        self.state.lock().unwrap().inner.clone()
+    }
 
-        let mut client = Client::new(auth).await.unwrap();
+    /// The name of this session. It changes when the session is refreshed.
+    pub fn name(&self) -> String {
+        self.inner().name.clone()
+    }
 
-        let test_table = Table::new("bigquery-public-data", "london_bicycles", "cycle_stations");
+    /// The snapshot time the API reads the table at, as reported in the session it created,
+    /// or else as requested. `None` if no snapshot time was requested and the API did not
+    /// report it: the table is then read as of when the session was created, which the
+    /// API does not tell. To know it, request it, e.g. with
+    /// [`ReadSessionBuilder::as_of_now`](ReadSessionBuilder::as_of_now).
+    pub fn snapshot_time(&self) -> Option<SystemTime> {
+        self.snapshot_timestamp().as_ref().and_then(system_time)
+    }
 
-        let mut read_session = client
-            .read_session_builder(test_table)
-            .parent_project_id("openquery-public-testing".to_string())
-            .build()
-            .await
-            .unwrap();
+    fn snapshot_timestamp(&self) -> Option<Timestamp> {
+        self.inner()
+            .table_modifiers
+            .as_ref()
+            .and_then(|table_modifiers| table_modifiers.snapshot_time.clone())
+    }
 
-        let mut num_rows = 0;
+    /// When this session expires, after which its streams can no longer be read.
+    pub fn expire_time(&self) -> Option<SystemTime> {
+        self.inner().expire_time.as_ref().and_then(system_time)
+    }
 
-        while let Some(stream_reader) = read_session.next_stream().await.unwrap() {
-            let mut arrow_stream_reader = stream_reader.into_arrow_reader().await.unwrap();
-            while let Some(record_batch) = arrow_stream_reader.next() {
-                num_rows += record_batch.unwrap().num_rows();
+    /// The schema of the rows of this session, with the BigQuery types of its columns.
+    /// This is known as soon as the session is created, before any data is read.
+    pub fn schema(&self) -> Result<SessionSchema, Error> {
+        let schema = self
+            .inner()
+            .schema
+            .clone()
+            .ok_or_else(|| Error::invalid("read session without schema"))?;
+        SessionSchema::try_from(&SerializedSchema::from(schema))
+    }
+
+    /// Export this session, with the streams that have not been taken yet, into a
+    /// [`ReadSessionHandle`](crate::handle::ReadSessionHandle) that can be sent to other machines.
+    pub fn handle(&self) -> Result<ReadSessionHandle, Error> {
+        let state = self.state.lock().unwrap();
+        let mut inner = BigQueryReadSession::clone(&state.inner);
+        inner.streams = state.streams.clone();
+        drop(state);
+        ReadSessionHandle::try_from(inner)
+    }
+
+    /// Take the next stream in this read session, without opening it, as a
+    /// [`ReadStreamHandle`](crate::handle::ReadStreamHandle) that can be sent to a worker.
+    /// Returns `None` when all streams have been taken.
+    pub fn next_stream_handle(&self) -> Result<Option<ReadStreamHandle>, Error> {
+        self.take_stream()
+            .map(|name| self.stream_handle(name))
+            .transpose()
+    }
+
+    /// Take the next stream in this read session. Returns `None` when all streams have been taken.
+    pub async fn next_stream(&self) -> Result<Option<RowsStreamReader>, Error> {
+        while let Some(name) = self.take_stream() {
+            if let Some(stream_reader) = self.open_taken_stream(name).await? {
+                return Ok(Some(stream_reader));
             }
         }
+        Ok(None)
+    }
 
-        assert_eq!(num_rows, 789);
+    /// Whether this session was built with [`auto_refresh`](ReadSessionBuilder::auto_refresh).
+    pub(crate) fn refreshes(&self) -> bool {
+        self.refresh.is_some()
+    }
+
+    /// Recreate the expired session `expired`, unless another clone or reader already
+    /// did, handing its work over to the new session. Fails with `expired` if rows of
+    /// the expired session were delivered.
+    async fn refresh(&self, expired: SessionExpired) -> Result<(), Error> {
+        let refresh = match &self.refresh {
+            Some(refresh) => refresh,
+            None => return Err(Error::SessionExpired(expired)),
+        };
+        let _guard = refresh.lock.lock().await;
+
+        let claim = {
+            let state = self.state.lock().unwrap();
+            if state.inner.name != expired.session {
+                // Only replaced once its work was handed over
+                return Ok(());
+            }
+            state.claim.clone()
+        };
+        if claim.lock().unwrap().delivered {
+            return Err(Error::SessionExpired(expired));
+        }
+
+        let inner = self.client.create_read_session(refresh.req.clone()).await?;
+        {
+            // Rows may have been delivered while the session was recreated
+            let mut claim = claim.lock().unwrap();
+            if claim.delivered {
+                return Err(Error::SessionExpired(expired));
+            }
+            claim.retired = true;
+        }
+        *self.state.lock().unwrap() = SessionState::new(inner);
+        Ok(())
+    }
+
+    /// Open the stream `name`, taken from the queue of this session. If its session
+    /// expired and was refreshed, `None` is returned: the work of the stream is now
+    /// in the streams of the new session, to be taken instead.
+    pub(crate) async fn open_taken_stream(
+        &self,
+        name: String,
+    ) -> Result<Option<RowsStreamReader>, Error> {
+        let (inner, claim) = {
+            let state = self.state.lock().unwrap();
+            (state.inner.clone(), state.claim.clone())
+        };
+        if !name.starts_with(&format!("{}/", inner.name)) {
+            // Taken before the session was refreshed
+            return Ok(None);
+        }
+        match self.open(name, inner, claim).await {
+            Err(Error::SessionExpired(expired)) => {
+                self.refresh(expired).await?;
+                Ok(None)
+            }
+            result => result.map(Some),
+        }
+    }
+
+    /// Bound how long each call made for this session may take from now on, e.g. to
+    /// open the next stream and read it, see [`Client::with_timeout`](Client::with_timeout).
+    /// Clones of a session share its streams, so a clone can be given a different timeout.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.client.limits.timeout = Some(timeout);
+        self
+    }
+
+    /// Fail reads of the streams of this session when no response is received for
+    /// `idle_timeout`, see [`Client::with_idle_timeout`](Client::with_idle_timeout).
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.client.limits.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Abort the calls made for this session, and the streams being read, when
+    /// `cancellation` is cancelled, see [`Client::with_cancellation`](Client::with_cancellation).
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.client.limits.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Create a [`ParallelReader`](crate::parallel::ParallelReader) that reads the
+    /// remaining streams of this session concurrently, merging them into a single stream.
+    pub fn parallel_reader(&self) -> ParallelReader {
+        ParallelReader::new(self.clone())
+    }
+
+    /// A handle to the stream `name` of this session.
+    fn stream_handle(&self, name: String) -> Result<ReadStreamHandle, Error> {
+        let mut inner = BigQueryReadSession::clone(&self.inner());
+        inner.streams = vec![ReadStream { name }];
+        let handle = ReadSessionHandle::try_from(inner)?;
+        handle
+            .stream_handles()
+            .pop()
+            .ok_or(Error::invalid("no stream handle"))
+    }
+
+    /// Split `stream` at `fraction`, see [`StreamSplitter::split`](crate::read::StreamSplitter::split).
+    async fn split_stream(
+        &self,
+        stream: String,
+        fraction: f64,
+    ) -> Result<Option<(String, ReadStreamHandle)>, Error> {
+        let req = SplitReadStreamRequest {
+            name: stream,
+            fraction,
+        };
+        let mut client = self.client.clone();
+        let deadline = client.limits.deadline();
+        let split = client.split_read_stream(req, deadline);
+
+        let SplitReadStreamResponse {
+            primary_stream,
+            remainder_stream,
+        } = self.client.limits.run(deadline, split).await??;
+
+        match (primary_stream, remainder_stream) {
+            (Some(primary), Some(remainder)) => {
+                let remainder = self.stream_handle(remainder.name)?;
+                Ok(Some((primary.name, remainder)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Split the streams of this session for the [`StreamSplitter`](crate::read::StreamSplitter)s
+    /// of their readers.
+    fn split(&self) -> Split {
+        let session = self.clone();
+        Box::new(move |stream, fraction| {
+            let session = session.clone();
+            async move { session.split_stream(stream, fraction).await }.boxed()
+        })
+    }
+
+    /// Open the remainders of the splits of the streams of this session.
+    fn open_remainder(&self) -> Open {
+        let session = self.clone();
+        Box::new(move |stream| {
+            let session = session.clone();
+            async move { session.open_stream(stream).await }.boxed()
+        })
+    }
+
+    /// Hand the work of the streams of `inner` over to a refreshed session, when it expired.
+    fn handover(
+        &self,
+        inner: Arc<BigQueryReadSession>,
+        claim: Arc<Mutex<SessionClaim>>,
+    ) -> Handover {
+        let session = self.clone();
+        let refresh = Box::new(move || {
+            let session = session.clone();
+            let expired = SessionExpired::new(&inner);
+            async move { session.refresh(expired).await }.boxed()
+        });
+        Handover { claim, refresh }
+    }
+
+    pub(crate) fn take_stream(&self) -> Option<String> {
+        let next = self.state.lock().unwrap().streams.pop();
+        next.map(|ReadStream { name }| name)
+    }
+
+    pub(crate) async fn open_stream(&self, name: String) -> Result<RowsStreamReader, Error> {
+        let (inner, claim) = {
+            let state = self.state.lock().unwrap();
+            (state.inner.clone(), state.claim.clone())
+        };
+        self.open(name, inner, claim).await
+    }
+
+    /// Open the stream `name` of the session `inner`, whose rows are claimed with `claim`.
+    async fn open(
+        &self,
+        name: String,
+        inner: Arc<BigQueryReadSession>,
+        claim: Arc<Mutex<SessionClaim>>,
+    ) -> Result<RowsStreamReader, Error> {
+        let client = &self.client;
+        let deadline = client.limits.deadline();
+        let open = client.retry_policy.retry(|| {
+            let mut client = client.clone();
+            let name = name.clone();
+            async move { client.read_stream_rows(&name, 0, deadline).await }
+        });
+        let opened = client.limits.run(deadline, open).await;
+        let rows_stream = match opened.and_then(|opened| opened) {
+            Ok(rows_stream) => rows_stream,
+            Err(Error::Status(status)) if is_session_expired(&status) => {
+                return Err(Error::SessionExpired(SessionExpired::new(&inner)));
+            }
+            Err(e) => return Err(e),
+        };
+        let schema = inner
+            .schema
+            .clone()
+            .ok_or(Error::invalid("empty schema response"))?;
+        let reconnect = self.client.reconnect(deadline);
+        let retry_policy = self.client.retry_policy.clone();
+        let limits = self.client.limits.clone();
+        Ok(RowsStreamReader::new(
+            name,
+            schema,
+            rows_stream,
+            reconnect,
+            retry_policy,
+            limits,
+            deadline,
+        )
+        .with_metrics(self.client.metrics.clone())
+        .with_split(self.split(), self.open_remainder())
+        .with_handover(self.handover(inner, claim)))
+    }
+}
+
+/// A builder for [`Client`](Client), to customize the endpoint and the transport.
+/// Create it with [`Client::builder`](Client::builder).
+pub struct ClientBuilder {
+    auth: Arc<dyn TokenProvider>,
+    endpoint: String,
+    plaintext: bool,
+    domain_name: Option<String>,
+    ca_certificate: Option<Certificate>,
+    tcp_keepalive: Option<Duration>,
+    http2_keep_alive_interval: Option<Duration>,
+    keep_alive_timeout: Option<Duration>,
+    keep_alive_while_idle: Option<bool>,
+    connect_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    retry_policy: RetryPolicy,
+    metrics: Option<Arc<dyn Metrics>>,
+}
+
+impl ClientBuilder {
+    fn new(auth: Arc<dyn TokenProvider>) -> Self {
+        Self {
+            auth,
+            endpoint: API_ENDPOINT.to_string(),
+            plaintext: false,
+            domain_name: None,
+            ca_certificate: None,
+            tcp_keepalive: None,
+            http2_keep_alive_interval: None,
+            keep_alive_timeout: None,
+            keep_alive_while_idle: None,
+            connect_timeout: None,
+            request_timeout: None,
+            retry_policy: RetryPolicy::default(),
+            metrics: None,
+        }
+    }
+
+    /// Sets the URI of the API endpoint, e.g. a [regional endpoint](https://cloud.google.com/bigquery/docs/reference/storage#regional_endpoints),
+    /// a Private Service Connect endpoint or an emulator. Defaults to `https://bigquerystorage.googleapis.com`.
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+
+    /// Connect without TLS. The endpoint is expected to use the `http` scheme.
+    pub fn plaintext(mut self) -> Self {
+        self.plaintext = true;
+        self
+    }
+
+    /// Sets the domain name the server certificate is verified against. Defaults to the
+    /// host of the endpoint.
+    pub fn domain_name(mut self, domain_name: impl Into<String>) -> Self {
+        self.domain_name = Some(domain_name.into());
+        self
+    }
+
+    /// Trust the CA certificates in `pem`, on top of the system roots.
+    pub fn ca_certificate(mut self, pem: impl AsRef<[u8]>) -> Self {
+        self.ca_certificate = Some(Certificate::from_pem(pem));
+        self
+    }
+
+    /// Sets the TCP keepalive interval of the connection.
+    pub fn tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Sets the interval at which HTTP/2 PING frames are sent to keep the connection alive.
+    pub fn http2_keep_alive_interval(mut self, interval: Duration) -> Self {
+        self.http2_keep_alive_interval = Some(interval);
+        self
+    }
+
+    /// Sets how long to wait for the acknowledgement of a keepalive PING before closing the connection.
+    pub fn keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.keep_alive_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets whether keepalive PINGs are also sent when there is no request in flight.
+    pub fn keep_alive_while_idle(mut self, enabled: bool) -> Self {
+        self.keep_alive_while_idle = Some(enabled);
+        self
+    }
+
+    /// Sets how long to wait for the connection to be established in [`build`](ClientBuilder::build).
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long to wait for the response to each request. For `ReadRows`, this
+    /// bounds the time until the stream starts, not the time to read all of it.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the [`RetryPolicy`](crate::retry::RetryPolicy) of the client.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sets the [`Metrics`](crate::metrics::Metrics) hook of the client.
+    pub fn metrics(mut self, metrics: Arc<dyn Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Connect to the endpoint and build the [`Client`](Client).
+    pub async fn build(self) -> Result<Client, Error> {
+        let mut endpoint = Endpoint::new(self.endpoint)?;
+
+        if !self.plaintext {
+            let mut tls_config = ClientTlsConfig::new();
+            if let Some(domain_name) = self.domain_name {
+                tls_config = tls_config.domain_name(domain_name);
+            }
+            if let Some(ca_certificate) = self.ca_certificate {
+                tls_config = tls_config.ca_certificate(ca_certificate);
+            }
+            endpoint = endpoint.tls_config(tls_config)?;
+        }
+
+        endpoint = endpoint.tcp_keepalive(self.tcp_keepalive);
+        if let Some(interval) = self.http2_keep_alive_interval {
+            endpoint = endpoint.http2_keep_alive_interval(interval);
+        }
+        if let Some(timeout) = self.keep_alive_timeout {
+            endpoint = endpoint.keep_alive_timeout(timeout);
+        }
+        if let Some(enabled) = self.keep_alive_while_idle {
+            endpoint = endpoint.keep_alive_while_idle(enabled);
+        }
+        if let Some(timeout) = self.request_timeout {
+            endpoint = endpoint.timeout(timeout);
+        }
+
+        let channel = match self.connect_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, endpoint.connect()).await??,
+            None => endpoint.connect().await?,
+        };
+
+        let mut client = Client::from_channel(Some(self.auth), channel);
+        client.metrics = self.metrics;
+        Ok(client.with_retry_policy(self.retry_policy))
+    }
+}
+
+/// The main object of this crate.
+///
+/// Cloning a `Client` is cheap: clones share the same token provider and connection.
+pub struct Client {
+    auth: Option<Arc<dyn TokenProvider>>,
+    big_query_read_client: BigQueryReadClient<Channel>,
+    big_query_write_client: BigQueryWriteClient<Channel>,
+    retry_policy: RetryPolicy,
+    limits: CallLimits,
+    metrics: Option<Arc<dyn Metrics>>,
+}
+
+impl Clone for Client {
+    fn clone(&self) -> Self {
+        Self {
+            auth: self.auth.clone(),
+            big_query_read_client: self.big_query_read_client.clone(),
+            big_query_write_client: self.big_query_write_client.clone(),
+            retry_policy: self.retry_policy.clone(),
+            limits: self.limits.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+impl Client {
+    /// Create a new client using `auth` as a token generator, connected to the
+    /// default endpoint.
+    pub async fn new<A: TokenProvider + 'static>(auth: A) -> Result<Self, Error> {
+        Self::builder(auth).build().await
+    }
+
+    /// Create a new [`ClientBuilder`](ClientBuilder) using `auth` as a token generator.
+    pub fn builder<A: TokenProvider + 'static>(auth: A) -> ClientBuilder {
+        ClientBuilder::new(Arc::new(auth))
+    }
+
+    /// Create a client over an established `channel`. Requests are not
+    /// authenticated if `auth` is `None`.
+    pub(crate) fn from_channel(auth: Option<Arc<dyn TokenProvider>>, channel: Channel) -> Self {
+        let big_query_read_client = BigQueryReadClient::new(channel.clone());
+        let big_query_write_client = BigQueryWriteClient::new(channel);
+        Self {
+            auth,
+            big_query_read_client,
+            big_query_write_client,
+            retry_policy: RetryPolicy::default(),
+            limits: CallLimits::default(),
+            metrics: None,
+        }
+    }
+
+    /// Set the [`RetryPolicy`](crate::retry::RetryPolicy) used to create read sessions,
+    /// and to open and resume read streams, after transient failures. Defaults to [`RetryPolicy::default`](crate::retry::RetryPolicy::default).
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Set the [`Metrics`](crate::metrics::Metrics) hook receiving the latency of the
+    /// calls of this client, and the metrics of the responses of the streams it reads.
+    pub fn with_metrics(mut self, metrics: Arc<dyn Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Bound how long each call of this client may take, retries included: creating
+    /// a read session, opening a stream with
+    /// [`ReadSession::next_stream`](ReadSession::next_stream), and reading it to the
+    /// end, e.g. with [`RowsStreamReader::into_arrow_reader`](crate::read::RowsStreamReader::into_arrow_reader).
+    /// The server is told about the deadline with the `grpc-timeout` header, and calls
+    /// past it fail with [`Error::Timeout`](crate::Error::Timeout).
+    ///
+    /// Clones of a client are cheap, so different calls can be given different timeouts.
+    /// Unlike [`ClientBuilder::request_timeout`](ClientBuilder::request_timeout), this
+    /// bounds the time to read whole streams.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.limits.timeout = Some(timeout);
+        self
+    }
+
+    /// Fail reads with [`Error::Timeout`](crate::Error::Timeout) when no response is
+    /// received from a stream for `idle_timeout`, to detect streams that hang.
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.limits.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Abort the calls of this client, and the streams it reads, when `cancellation` is
+    /// cancelled. They then fail with an error of kind
+    /// [`ErrorKind::Cancelled`](crate::error::ErrorKind::Cancelled).
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.limits.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Recreate a [`ReadSession`](ReadSession) from its [`ReadSessionHandle`](crate::handle::ReadSessionHandle),
+    /// for example on another machine. This does not hit Google's API.
+    pub fn read_session(&self, handle: &ReadSessionHandle) -> ReadSession {
+        ReadSession::new(self.clone(), handle.to_session())
+    }
+
+    /// Open a single stream of a read session from its [`ReadStreamHandle`](crate::handle::ReadStreamHandle).
+    pub async fn read_stream(&self, handle: &ReadStreamHandle) -> Result<RowsStreamReader, Error> {
+        let session = ReadSession::new(self.clone(), handle.to_session());
+        session.open_stream(handle.stream().to_string()).await
+    }
+
+    /// Create a new [`ReadSessionBuilder`](ReadSessionBuilder).
+    pub fn read_session_builder(&self, table: Table) -> ReadSessionBuilder {
+        ReadSessionBuilder::new(self.clone(), table)
+    }
+
+    /// Create a [`StreamWriter`](crate::write::StreamWriter) appending rows described by
+    /// `schema` to the `_default` stream of `table`. Rows appended to the default stream
+    /// are committed immediately, with at-least-once semantics.
+    pub fn default_stream_writer(&self, table: &Table, schema: ProtoSchema) -> StreamWriter {
+        let write_stream = format!("{}/streams/_default", table);
+        StreamWriter::new(self.clone(), write_stream, schema)
+    }
+
+    /// Append `rows`, protocol buffer messages described by `schema`, to the `_default`
+    /// stream of `table`. To append several times, prefer keeping a writer from
+    /// [`Client::default_stream_writer`](Client::default_stream_writer).
+    pub async fn append_rows(
+        &self,
+        table: &Table,
+        schema: ProtoSchema,
+        rows: Vec<Vec<u8>>,
+    ) -> Result<AppendedRows, Error> {
+        self.default_stream_writer(table, schema).append(rows).await
+    }
+
+    /// Append the rows of `batch` to the `_default` stream of `table`, described by a
+    /// schema built from the schema of `batch` with [`proto_schema_from_arrow`](crate::write::proto_schema_from_arrow).
+    #[cfg(feature = "arrow")]
+    pub async fn append_record_batch(
+        &self,
+        table: &Table,
+        batch: &RecordBatch,
+    ) -> Result<AppendedRows, Error> {
+        let schema = proto_schema_from_arrow(&batch.schema())?;
+        self.default_stream_writer(table, schema)
+            .append_record_batch(batch)
+            .await
+    }
+
+    /// Create a [`WriteSession`](crate::write::WriteSession) creating streams of type
+    /// `stream_type` on `table`. This does not hit Google's API.
+    pub fn write_session(&self, table: &Table, stream_type: WriteStreamType) -> WriteSession {
+        WriteSession::new(self.clone(), table.to_string(), stream_type)
+    }
+    async fn new_request<D>(&self, t: D, params: &str) -> Result<Request<D>, Error> {
+        let mut req = Request::new(t);
+        if let Some(auth) = &self.auth {
+            let scopes = [API_SCOPE];
+            let fetch_token = auth.token(&scopes);
+            let span = span!(DEBUG, "fetch_token");
+            let token = observe(&self.metrics, Call::FetchToken, span, fetch_token).await?;
+            let bearer_token = format!("Bearer {}", token);
+            let bearer_value = MetadataValue::from_str(&bearer_token)?;
+            req.metadata_mut().insert("authorization", bearer_value);
+        }
+        let meta = req.metadata_mut();
+        meta.insert("x-goog-request-params", MetadataValue::from_str(params)?);
+        Ok(req)
+    }
+    async fn create_read_session(
+        &self,
+        req: CreateReadSessionRequest,
+    ) -> Result<BigQueryReadSession, Error> {
+        let deadline = self.limits.deadline();
+        let attempts = self.retry_policy.retry(|| {
+            let mut client = self.clone();
+            let req = req.clone();
+            async move { client.try_create_read_session(req, deadline).await }
+        });
+        self.limits.run(deadline, attempts).await?
+    }
+    async fn try_create_read_session(
+        &mut self,
+        req: CreateReadSessionRequest,
+        deadline: Option<Instant>,
+    ) -> Result<BigQueryReadSession, Error> {
+        let table_uri = &req.read_session.as_ref().unwrap().table;
+        let params = format!("read_session.table={}", table_uri);
+        let span = span!(DEBUG, "create_read_session", table = %table_uri);
+        // Keep the requested snapshot time, the only one known, if the API does not echo it
+        let table_modifiers = req.read_session.as_ref().unwrap().table_modifiers.clone();
+        let mut wrapped = self.new_request(req, &params).await?;
+        set_deadline(&mut wrapped, deadline)?;
+
+        let create = self.big_query_read_client.create_read_session(wrapped);
+        let mut read_session = observe(&self.metrics, Call::CreateReadSession, span, create)
+            .await?
+            .into_inner();
+        if read_session.table_modifiers.is_none() {
+            read_session.table_modifiers = table_modifiers;
+        }
+        Ok(read_session)
+    }
+    async fn read_stream_rows(
+        &mut self,
+        stream: &str,
+        offset: i64,
+        deadline: Option<Instant>,
+    ) -> Result<Streaming<ReadRowsResponse>, Error> {
+        let req = ReadRowsRequest {
+            read_stream: stream.to_string(),
+            offset,
+        };
+        let params = format!("read_stream={}", req.read_stream);
+        let span = span!(DEBUG, "read_rows", stream = %req.read_stream, offset);
+        let mut wrapped = self.new_request(req, &params).await?;
+        set_deadline(&mut wrapped, deadline)?;
+        let read = self.big_query_read_client.read_rows(wrapped);
+        let read_rows_response = observe(&self.metrics, Call::ReadRows, span, read)
+            .await?
+            .into_inner();
+        Ok(read_rows_response)
+    }
+    async fn split_read_stream(
+        &mut self,
+        req: SplitReadStreamRequest,
+        deadline: Option<Instant>,
+    ) -> Result<SplitReadStreamResponse, Error> {
+        let params = format!("name={}", req.name);
+        let span = span!(DEBUG, "split_read_stream", stream = %req.name, fraction = req.fraction);
+        let mut wrapped = self.new_request(req, &params).await?;
+        set_deadline(&mut wrapped, deadline)?;
+
+        let split = self.big_query_read_client.split_read_stream(wrapped);
+        let split_read_stream_response = observe(&self.metrics, Call::SplitReadStream, span, split)
+            .await?
+            .into_inner();
+        Ok(split_read_stream_response)
+    }
+    pub(crate) async fn create_write_stream(
+        &mut self,
+        req: CreateWriteStreamRequest,
+    ) -> Result<WriteStream, Error> {
+        let params = format!("parent={}", req.parent);
+        let span = span!(DEBUG, "create_write_stream", table = %req.parent);
+        let wrapped = self.new_request(req, &params).await?;
+
+        let create = self.big_query_write_client.create_write_stream(wrapped);
+        let write_stream = observe(&self.metrics, Call::CreateWriteStream, span, create)
+            .await?
+            .into_inner();
+        Ok(write_stream)
+    }
+    pub(crate) async fn finalize_write_stream(
+        &mut self,
+        req: FinalizeWriteStreamRequest,
+    ) -> Result<FinalizeWriteStreamResponse, Error> {
+        let params = format!("name={}", req.name);
+        let span = span!(DEBUG, "finalize_write_stream", stream = %req.name);
+        let wrapped = self.new_request(req, &params).await?;
+
+        let finalize = self.big_query_write_client.finalize_write_stream(wrapped);
+        let finalize_write_stream_response =
+            observe(&self.metrics, Call::FinalizeWriteStream, span, finalize)
+                .await?
+                .into_inner();
+        Ok(finalize_write_stream_response)
+    }
+    pub(crate) async fn batch_commit_write_streams(
+        &mut self,
+        req: BatchCommitWriteStreamsRequest,
+    ) -> Result<BatchCommitWriteStreamsResponse, Error> {
+        let params = format!("parent={}", req.parent);
+        let span = span!(DEBUG, "batch_commit_write_streams", table = %req.parent);
+        let wrapped = self.new_request(req, &params).await?;
+
+        let commit = self
+            .big_query_write_client
+            .batch_commit_write_streams(wrapped);
+        let batch_commit_write_streams_response =
+            observe(&self.metrics, Call::BatchCommitWriteStreams, span, commit)
+                .await?
+                .into_inner();
+        Ok(batch_commit_write_streams_response)
+    }
+    pub(crate) async fn flush_rows(
+        &mut self,
+        req: FlushRowsRequest,
+    ) -> Result<FlushRowsResponse, Error> {
+        let params = format!("write_stream={}", req.write_stream);
+        let span = span!(DEBUG, "flush_rows", stream = %req.write_stream, offset = ?req.offset);
+        let wrapped = self.new_request(req, &params).await?;
+
+        let flush = self.big_query_write_client.flush_rows(wrapped);
+        let flush_rows_response = observe(&self.metrics, Call::FlushRows, span, flush)
+            .await?
+            .into_inner();
+        Ok(flush_rows_response)
+    }
+    pub(crate) async fn append_rows_connection(
+        &mut self,
+        first: AppendRowsRequest,
+    ) -> Result<AppendRowsConnection, Error> {
+        let params = format!("write_stream={}", first.write_stream);
+        let span = span!(DEBUG, "append_rows", stream = %first.write_stream);
+        let (requests, receiver) = mpsc::unbounded();
+        // The first request is sent along with the call, as the API waits for it
+        // before responding
+        let wrapped = self
+            .new_request(stream::iter(Some(first)).chain(receiver), &params)
+            .await?;
+
+        let append = self.big_query_write_client.append_rows(wrapped);
+        let responses = observe(&self.metrics, Call::AppendRows, span, append)
+            .await?
+            .into_inner();
+        Ok(AppendRowsConnection {
+            requests,
+            responses,
+        })
+    }
+    fn reconnect(&self, deadline: Option<Instant>) -> Reconnect {
+        let client = self.clone();
+        Box::new(move |stream, offset| {
+            let mut client = client.clone();
+            let stream = stream.to_string();
+            async move { client.read_stream_rows(&stream, offset, deadline).await }.boxed()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::stream::StreamExt;
+
+    #[cfg(feature = "arrow")]
+    use crate::StreamOrdering;
+
+    #[test]
+    fn convert_snapshot_times() {
+        let time = std::time::UNIX_EPOCH + Duration::new(1_704_067_200, 5);
+        let timestamp = Timestamp {
+            seconds: 1_704_067_200,
+            nanos: 5,
+        };
+        assert_eq!(
+            SnapshotTime::from(time),
+            SnapshotTime::from(timestamp.clone())
+        );
+
+        #[cfg(feature = "chrono")]
+        {
+            use chrono::TimeZone;
+            let datetime = chrono::Utc.timestamp_opt(1_704_067_200, 5).unwrap();
+            assert_eq!(SnapshotTime::from(datetime), SnapshotTime::from(timestamp));
+
+            let leap_second = chrono::Utc
+                .timestamp_opt(1_704_067_199, 1_500_000_000)
+                .unwrap();
+            let timestamp = Timestamp {
+                seconds: 1_704_067_199,
+                nanos: 999_999_999,
+            };
+            assert_eq!(
+                SnapshotTime::from(leap_second),
+                SnapshotTime::from(timestamp)
+            );
+        }
+    }
+
+    #[test]
+    fn parse_tables() {
+        let table = Table::new("my-project", "my_dataset", "my_table");
+        for s in &[
+            "my-project.my_dataset.my_table",
+            "my-project:my_dataset.my_table",
+            "projects/my-project/datasets/my_dataset/tables/my_table",
+            "`my-project.my_dataset.my_table`",
+        ] {
+            assert_eq!(s.parse::<Table>().unwrap(), table);
+        }
+
+        let table: Table = "example.com:my-project.my_dataset.my_table$20240101"
+            .parse()
+            .unwrap();
+        assert_eq!(table.project_id(), "example.com:my-project");
+        assert_eq!(table.dataset_id(), "my_dataset");
+        assert_eq!(table.table_id(), "my_table");
+        assert_eq!(table.partition(), Some("20240101"));
+        assert_eq!(
+            table.to_string(),
+            "projects/example.com:my-project/datasets/my_dataset/tables/my_table$20240101"
+        );
+        assert_eq!(table, Table::try_from(table.to_string()).unwrap());
+
+        for s in &[
+            "my_dataset.my_table",
+            "My-Project.my_dataset.my_table",
+            "my-project.my-dataset.my_table",
+            "my-project.my_dataset.my_table;",
+            "my-project.my_dataset.my_table$2024-01-01",
+            "projects/my-project/tables/my_table",
+        ] {
+            assert!(s.parse::<Table>().is_err(), "{} should be rejected", s);
+        }
+    }
+
+    /// Create a session reading the public `london_bicycles.cycle_stations` table, with
+    /// the service account key in `clientsecret.json`. Returns `None`, so that the test
+    /// is skipped, when there is no key.
+    #[cfg(any(feature = "arrow", feature = "avro"))]
+    async fn cycle_stations_session(data_format: DataFormat) -> Option<ReadSession> {
+        if !std::path::Path::new("clientsecret.json").exists() {
+            eprintln!("skipped: no clientsecret.json to read BigQuery with");
+            return None;
+        }
+        let sa_key = yup_oauth2::read_service_account_key("clientsecret.json")
+            .await
+            .unwrap();
+        let auth = yup_oauth2::ServiceAccountAuthenticator::builder(sa_key)
+            .build()
+            .await
+            .unwrap();
+
+        let client = Client::new(auth).await.unwrap();
+
+        let test_table = Table::new("bigquery-public-data", "london_bicycles", "cycle_stations");
+
+        let read_session = client
+            .read_session_builder(test_table)
+            .parent_project_id("openquery-public-testing".to_string())
+            .data_format(data_format)
+            .build()
+            .await
+            .unwrap();
+        Some(read_session)
+    }
+
+    #[cfg(feature = "arrow")]
+    #[tokio::test]
+    async fn read_a_table_with_arrow() {
+        let read_session = match cycle_stations_session(DataFormat::Arrow).await {
+            Some(read_session) => read_session,
+            None => return,
+        };
+
+        let mut num_rows = 0;
+
+        while let Some(stream_reader) = read_session.next_stream().await.unwrap() {
+            let arrow_stream_reader = stream_reader.into_arrow_reader().await.unwrap();
+            for record_batch in arrow_stream_reader {
+                num_rows += record_batch.unwrap().num_rows();
+            }
+        }
+
+        assert_eq!(num_rows, 789);
+    }
+
+    #[cfg(feature = "arrow")]
+    #[tokio::test]
+    async fn read_a_table_with_record_batch_stream() {
+        let read_session = match cycle_stations_session(DataFormat::Arrow).await {
+            Some(read_session) => read_session,
+            None => return,
+        };
+
+        let mut num_rows = 0;
+
+        while let Some(stream_reader) = read_session.next_stream().await.unwrap() {
+            let mut record_batch_stream = stream_reader.into_record_batch_stream().unwrap();
+            while let Some(record_batch) = record_batch_stream.next().await {
+                num_rows += record_batch.unwrap().num_rows();
+            }
+        }
+
+        assert_eq!(num_rows, 789);
+    }
+
+    #[cfg(feature = "arrow")]
+    #[tokio::test]
+    async fn read_a_table_with_parallel_reader() {
+        let read_session = match cycle_stations_session(DataFormat::Arrow).await {
+            Some(read_session) => read_session,
+            None => return,
+        };
+
+        let mut record_batch_stream = read_session
+            .parallel_reader()
+            .concurrency(4)
+            .ordering(StreamOrdering::ByStream)
+            .into_record_batch_stream();
+
+        let mut num_rows = 0;
+        while let Some(record_batch) = record_batch_stream.next().await {
+            num_rows += record_batch.unwrap().num_rows();
+        }
+
+        assert_eq!(num_rows, 789);
+    }
+
+    #[cfg(feature = "avro")]
+    #[tokio::test]
+    async fn read_a_table_with_avro() {
+        let read_session = match cycle_stations_session(DataFormat::Avro).await {
+            Some(read_session) => read_session,
+            None => return,
+        };
+
+        let mut num_rows = 0;
+
+        while let Some(stream_reader) = read_session.next_stream().await.unwrap() {
+            let mut avro_row_stream = stream_reader.into_avro_stream().unwrap();
+            while let Some(row) = avro_row_stream.next().await {
+                row.unwrap();
+                num_rows += 1;
+            }
+        }
+
+        assert_eq!(num_rows, 789);
+    }
+
+    #[cfg(all(feature = "testing", feature = "arrow"))]
+    mod fake {
+        use super::*;
+        use crate::testing::{FakeServer, FakeServerBuilder};
+
+        use arrow::array::Int64Array;
+        use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+        use arrow::record_batch::RecordBatch;
+
+        fn test_table() -> Table {
+            Table::new("fake-project", "fake_dataset", "fake_table")
+        }
+
+        fn seeded(num_batches: i64) -> FakeServerBuilder {
+            let schema: SchemaRef =
+                Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+            let batches = (0..num_batches)
+                .map(|i| {
+                    let ids = Int64Array::from(vec![3 * i, 3 * i + 1, 3 * i + 2]);
+                    RecordBatch::try_new(schema.clone(), vec![Arc::new(ids)]).unwrap()
+                })
+                .collect();
+            FakeServer::builder()
+                .arrow_table(test_table(), schema, batches)
+                .unwrap()
+        }
+
+        fn batch_ids(record_batch: &RecordBatch) -> Vec<i64> {
+            let column = record_batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .unwrap();
+            (0..column.len()).map(|i| column.value(i)).collect()
+        }
+
+        async fn read_ids(stream_reader: RowsStreamReader) -> Vec<i64> {
+            let mut record_batch_stream = stream_reader.into_record_batch_stream().unwrap();
+            let mut ids = Vec::new();
+            while let Some(record_batch) = record_batch_stream.next().await {
+                ids.extend(batch_ids(&record_batch.unwrap()));
+            }
+            ids
+        }
+
+        #[tokio::test]
+        async fn read_all_streams() {
+            let server = seeded(4).start().await.unwrap();
+            let client = server.client().await.unwrap();
+
+            let read_session = client
+                .read_session_builder(test_table())
+                .max_stream_count(2)
+                .build()
+                .await
+                .unwrap();
+
+            let mut ids = Vec::new();
+            while let Some(stream_reader) = read_session.next_stream().await.unwrap() {
+                ids.extend(read_ids(stream_reader).await);
+            }
+            ids.sort();
+
+            assert_eq!(ids, (0..12).collect::<Vec<_>>());
+        }
+
+        #[tokio::test]
+        async fn session_schema() {
+            use crate::{FieldMode, FieldType};
+
+            let server = seeded(1).start().await.unwrap();
+            let client = server.client().await.unwrap();
+
+            let read_session = client
+                .read_session_builder(test_table())
+                .build()
+                .await
+                .unwrap();
+
+            let schema = read_session.schema().unwrap();
+            let id = schema.field("id").unwrap();
+            assert_eq!(id.field_type(), FieldType::Int64);
+            assert_eq!(id.mode(), FieldMode::Required);
+            assert_eq!(schema.fields().len(), 1);
+        }
+
+        #[tokio::test]
+        async fn read_at_the_same_snapshot() {
+            let server = seeded(1).start().await.unwrap();
+            let client = server.client().await.unwrap();
+
+            let first = client
+                .read_session_builder(test_table())
+                .build()
+                .await
+                .unwrap();
+            assert_eq!(first.snapshot_time(), None);
+
+            let first = client
+                .read_session_builder(test_table())
+                .as_of_now(SNAPSHOT_MARGIN)
+                .build()
+                .await
+                .unwrap();
+            let second = client
+                .read_session_builder(test_table())
+                .same_snapshot_as(&first)
+                .build()
+                .await
+                .unwrap();
+
+            let snapshot_time = first.snapshot_time().unwrap();
+            assert!(snapshot_time <= std::time::SystemTime::now() - SNAPSHOT_MARGIN);
+            assert_eq!(second.snapshot_time(), first.snapshot_time());
+        }
+
+        #[tokio::test]
+        async fn report_expired_session() {
+            let server = seeded(4).expire_sessions(1).start().await.unwrap();
+            let client = server.client().await.unwrap();
+
+            let read_session = client
+                .read_session_builder(test_table())
+                .build()
+                .await
+                .unwrap();
+            assert!(read_session.expire_time().unwrap() < std::time::SystemTime::now());
+
+            match read_session.next_stream().await {
+                Err(Error::SessionExpired(e)) => assert_eq!(e.session(), read_session.name()),
+                _ => panic!("expected the session to be expired"),
+            }
+        }
+
+        #[tokio::test]
+        async fn refresh_expired_session() {
+            let server = seeded(4).expire_sessions(1).start().await.unwrap();
+            let client = server.client().await.unwrap();
+
+            let read_session = client
+                .read_session_builder(test_table())
+                .max_stream_count(2)
+                .auto_refresh(true)
+                .build()
+                .await
+                .unwrap();
+            let expired = read_session.name();
+
+            let mut record_batch_stream = read_session.parallel_reader().into_record_batch_stream();
+            let mut ids = Vec::new();
+            while let Some(record_batch) = record_batch_stream.next().await {
+                ids.extend(batch_ids(&record_batch.unwrap()));
+            }
+            ids.sort();
+
+            assert_eq!(ids, (0..12).collect::<Vec<_>>());
+            assert_ne!(read_session.name(), expired);
+            assert!(read_session.expire_time().unwrap() > std::time::SystemTime::now());
+        }
+
+        #[tokio::test]
+        async fn refresh_session_of_opened_stream() {
+            let server = seeded(4).unavailable_after(0).start().await.unwrap();
+            let client = server.client().await.unwrap();
+
+            let read_session = client
+                .read_session_builder(test_table())
+                .max_stream_count(2)
+                .auto_refresh(true)
+                .build()
+                .await
+                .unwrap();
+            let expired = read_session.name();
+
+            // Opened, but no row was read from it when the session expires
+            let stream_reader = read_session.next_stream().await.unwrap().unwrap();
+            server.expire_sessions_now();
+            assert!(read_ids(stream_reader).await.is_empty());
+
+            let mut ids = Vec::new();
+            while let Some(stream_reader) = read_session.next_stream().await.unwrap() {
+                ids.extend(read_ids(stream_reader).await);
+            }
+            ids.sort();
+
+            assert_eq!(ids, (0..12).collect::<Vec<_>>());
+            assert_ne!(read_session.name(), expired);
+        }
+
+        #[tokio::test]
+        async fn report_expired_session_once_read() {
+            let server = seeded(4).unavailable_after(1).start().await.unwrap();
+            let client = server.client().await.unwrap();
+
+            let read_session = client
+                .read_session_builder(test_table())
+                .max_stream_count(1)
+                .auto_refresh(true)
+                .build()
+                .await
+                .unwrap();
+            let expired = read_session.name();
+
+            let stream_reader = read_session.next_stream().await.unwrap().unwrap();
+            server.expire_sessions_now();
+            let mut record_batch_stream = stream_reader.into_record_batch_stream().unwrap();
+            let record_batch = record_batch_stream.next().await.unwrap().unwrap();
+            assert_eq!(batch_ids(&record_batch), vec![0, 1, 2]);
+
+            // The rows left cannot be told apart in a new session
+            match record_batch_stream.next().await {
+                Some(Err(Error::SessionExpired(e))) => assert_eq!(e.session(), expired),
+                _ => panic!("expected the session to be expired"),
+            }
+            assert_eq!(read_session.name(), expired);
+        }
+
+        #[tokio::test]
+        async fn resume_after_transient_failure() {
+            let server = seeded(4).unavailable_after(2).start().await.unwrap();
+            let client = server.client().await.unwrap();
+
+            let read_session = client
+                .read_session_builder(test_table())
+                .max_stream_count(1)
+                .build()
+                .await
+                .unwrap();
+
+            let stream_reader = read_session.next_stream().await.unwrap().unwrap();
+            let progress = stream_reader.progress();
+            let ids = read_ids(stream_reader).await;
+
+            assert_eq!(ids, (0..12).collect::<Vec<_>>());
+            assert_eq!(progress.get().rows_read, 12);
+            assert_eq!(progress.get().fraction_consumed, 1.0);
+        }
+
+        #[tokio::test]
+        async fn retry_exhausted_quota() {
+            let retry_delay = Duration::from_millis(200);
+            let server = seeded(4)
+                .exhaust_quota(2, retry_delay)
+                .start()
+                .await
+                .unwrap();
+            let retry_policy = RetryPolicy::new().initial_backoff(Duration::from_millis(1));
+            let client = server
+                .client()
+                .await
+                .unwrap()
+                .with_retry_policy(retry_policy);
+
+            let start = std::time::Instant::now();
+            let read_session = client
+                .read_session_builder(test_table())
+                .build()
+                .await
+                .unwrap();
+
+            // Both retries waited for the delay asked by the server
+            assert!(start.elapsed() >= 2 * retry_delay);
+            assert!(read_session.next_stream().await.unwrap().is_some());
+        }
+
+        #[tokio::test]
+        async fn give_up_on_exhausted_quota() {
+            let server = seeded(4)
+                .exhaust_quota(2, Duration::from_secs(1))
+                .start()
+                .await
+                .unwrap();
+
+            let client = server
+                .client()
+                .await
+                .unwrap()
+                .with_retry_policy(RetryPolicy::never());
+            let error = client
+                .read_session_builder(test_table())
+                .build()
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(error.kind(), crate::ErrorKind::ResourceExhausted);
+
+            // The delay asked by the server is past the deadline
+            let client = server
+                .client()
+                .await
+                .unwrap()
+                .with_retry_policy(RetryPolicy::new().deadline(Duration::from_millis(500)));
+            let start = std::time::Instant::now();
+            let error = client
+                .read_session_builder(test_table())
+                .build()
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(error.retry_delay(), Some(Duration::from_secs(1)));
+            assert!(start.elapsed() < Duration::from_millis(500));
+        }
+
+        #[tokio::test]
+        async fn time_out_stalled_streams() {
+            let server = seeded(4).stall_after(2).start().await.unwrap();
+            let client = server.client().await.unwrap();
+
+            let read_session = client
+                .read_session_builder(test_table())
+                .max_stream_count(1)
+                .build()
+                .await
+                .unwrap();
+            let handle = read_session.handle().unwrap();
+
+            let stream_reader = read_session
+                .with_idle_timeout(Duration::from_millis(100))
+                .next_stream()
+                .await
+                .unwrap()
+                .unwrap();
+            let error = stream_reader.into_arrow_reader().await.err().unwrap();
+            assert_eq!(error.kind(), crate::ErrorKind::DeadlineExceeded);
+
+            // Reading the whole stream must be done within the timeout
+            let stream_reader = client
+                .with_timeout(Duration::from_millis(200))
+                .read_stream(&handle.stream_handles()[0])
+                .await
+                .unwrap();
+            let start = std::time::Instant::now();
+            let error = stream_reader.into_arrow_reader().await.err().unwrap();
+            assert!(matches!(error, Error::Timeout(_)));
+            assert!(start.elapsed() < Duration::from_secs(1));
+        }
+
+        #[tokio::test]
+        async fn cancel_a_stream() {
+            let server = seeded(4).stall_after(2).start().await.unwrap();
+            let cancellation = CancellationToken::new();
+            let client = server
+                .client()
+                .await
+                .unwrap()
+                .with_cancellation(cancellation.clone());
+
+            let read_session = client
+                .read_session_builder(test_table())
+                .max_stream_count(1)
+                .build()
+                .await
+                .unwrap();
+            let stream_reader = read_session.next_stream().await.unwrap().unwrap();
+            let mut record_batch_stream = stream_reader.into_record_batch_stream().unwrap();
+            for _ in 0..2 {
+                record_batch_stream.next().await.unwrap().unwrap();
+            }
+
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                cancellation.cancel();
+            });
+            let error = record_batch_stream.next().await.unwrap().unwrap_err();
+            assert_eq!(error.kind(), crate::ErrorKind::Cancelled);
+            assert!(record_batch_stream.next().await.is_none());
+        }
+
+        #[tokio::test]
+        async fn record_metrics() {
+            #[derive(Default)]
+            struct Recorded {
+                calls: Mutex<Vec<(Call, bool)>>,
+                responses: Mutex<Vec<crate::ResponseMetrics>>,
+            }
+
+            impl Metrics for Recorded {
+                fn record_call(&self, call: Call, _: Duration, error: Option<&Error>) {
+                    self.calls.lock().unwrap().push((call, error.is_none()));
+                }
+
+                fn record_response(&self, _: &str, response: &crate::ResponseMetrics) {
+                    self.responses.lock().unwrap().push(*response);
+                }
+            }
+
+            let server = seeded(4).start().await.unwrap();
+            let recorded = Arc::new(Recorded::default());
+            let client = server
+                .client()
+                .await
+                .unwrap()
+                .with_metrics(recorded.clone());
+
+            let read_session = client
+                .read_session_builder(test_table())
+                .max_stream_count(1)
+                .build()
+                .await
+                .unwrap();
+            let stream_reader = read_session.next_stream().await.unwrap().unwrap();
+            stream_reader.into_arrow_reader().await.unwrap();
+
+            assert_eq!(
+                *recorded.calls.lock().unwrap(),
+                vec![(Call::CreateReadSession, true), (Call::ReadRows, true)]
+            );
+            let responses = recorded.responses.lock().unwrap();
+            assert_eq!(responses.len(), 4);
+            assert_eq!(responses.iter().map(|r| r.rows).sum::<i64>(), 12);
+            assert!(responses.iter().all(|r| r.bytes > 0));
+        }
+
+        #[tokio::test]
+        async fn split_a_stream() {
+            let server = seeded(4).start().await.unwrap();
+            let client = server.client().await.unwrap();
+
+            let read_session = client
+                .read_session_builder(test_table())
+                .max_stream_count(1)
+                .build()
+                .await
+                .unwrap();
+            let handle = read_session.next_stream_handle().unwrap().unwrap();
+
+            let stream_reader = client.read_stream(&handle).await.unwrap();
+            let splitter = stream_reader.splitter();
+            let mut record_batch_stream = stream_reader.into_record_batch_stream().unwrap();
+            let record_batch = record_batch_stream.next().await.unwrap().unwrap();
+            let mut ids = batch_ids(&record_batch);
+            assert_eq!(ids, vec![0, 1, 2]);
+
+            // The first quarter of the stream was read, and would be read again
+            match splitter.split(0.2).await {
+                Err(Error::Usage(_)) => {}
+                _ => panic!("expected the split to be rejected"),
+            }
+
+            let remainder = splitter.split(0.5).await.unwrap().unwrap();
+            assert_eq!(remainder.session(), handle.session());
+
+            // The reader carries on with the primary stream, from the row it reached
+            while let Some(record_batch) = record_batch_stream.next().await {
+                ids.extend(batch_ids(&record_batch.unwrap()));
+            }
+            assert_eq!(ids, (0..6).collect::<Vec<_>>());
+
+            let remainder = client.read_stream(&remainder).await.unwrap();
+            assert_eq!(read_ids(remainder).await, (6..12).collect::<Vec<_>>());
+            // The original stream is not truncated by the split
+            let original = client.read_stream(&handle).await.unwrap();
+            assert_eq!(read_ids(original).await, (0..12).collect::<Vec<_>>());
+        }
+
+        #[tokio::test]
+        async fn split_into_readers() {
+            let server = seeded(4).start().await.unwrap();
+            let client = server.client().await.unwrap();
+
+            let read_session = client
+                .read_session_builder(test_table())
+                .max_stream_count(1)
+                .build()
+                .await
+                .unwrap();
+            let stream_reader = read_session.next_stream().await.unwrap().unwrap();
+            let original = stream_reader.stream_name().to_string();
+
+            let (primary, remainder) = stream_reader.split(0.5).await.unwrap();
+            let remainder = remainder.unwrap();
+            assert_ne!(primary.stream_name(), original);
+            assert_eq!(read_ids(primary).await, (0..6).collect::<Vec<_>>());
+            assert_eq!(read_ids(remainder).await, (6..12).collect::<Vec<_>>());
+        }
+
+        #[tokio::test]
+        async fn send_read_options() {
+            let server = seeded(1).start().await.unwrap();
+            let client = server.client().await.unwrap();
+
+            client
+                .read_session_builder(test_table())
+                .selected_fields(vec!["id".to_string()])
+                .row_restriction("id > 5".to_string())
+                .build()
+                .await
+                .unwrap();
+
+            let read_options = server.read_options();
+            assert_eq!(read_options.len(), 1);
+            assert_eq!(read_options[0].selected_fields, vec!["id"]);
+            assert_eq!(read_options[0].row_restriction, "id > 5");
+        }
+
+        #[cfg(feature = "serde")]
+        #[tokio::test]
+        async fn deserialize_rows() {
+            #[derive(serde::Deserialize)]
+            struct Row {
+                id: u32,
+            }
+
+            #[derive(Debug, serde::Deserialize)]
+            struct BadRow {
+                #[allow(dead_code)]
+                id: String,
+            }
+
+            let server = seeded(2).start().await.unwrap();
+            let client = server.client().await.unwrap();
+
+            let read_session = client
+                .read_session_builder(test_table())
+                .max_stream_count(1)
+                .build()
+                .await
+                .unwrap();
+            let handle = read_session.next_stream_handle().unwrap().unwrap();
+
+            let stream_reader = client.read_stream(&handle).await.unwrap();
+            let rows = stream_reader.into_deserialize::<Row>().unwrap();
+            let ids: Vec<u32> = rows.map(|row| row.unwrap().id).collect().await;
+            assert_eq!(ids, (0..6).collect::<Vec<_>>());
+
+            let stream_reader = client.read_stream(&handle).await.unwrap();
+            let mut rows = stream_reader.into_deserialize::<BadRow>().unwrap();
+            match rows.next().await {
+                Some(Err(Error::Deserialize(e))) => assert_eq!(e.column(), "id"),
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+
+        #[cfg(feature = "derive")]
+        #[tokio::test]
+        async fn read_as_derived_rows() {
+            use crate::BigQueryRow;
+
+            #[derive(BigQueryRow)]
+            struct Row {
+                #[bq(rename = "id")]
+                row_id: i64,
+                #[bq(rename = "id")]
+                maybe_id: Option<i64>,
+            }
+
+            assert_eq!(Row::selected_fields(), vec!["id"]);
+
+            let server = seeded(4).start().await.unwrap();
+            let client = server.client().await.unwrap();
+
+            let rows = client
+                .read_session_builder(test_table())
+                .max_stream_count(2)
+                .read_as::<Row>()
+                .await
+                .unwrap();
+            let mut ids: Vec<(i64, Option<i64>)> = rows
+                .map(|row| row.unwrap())
+                .map(|row| (row.row_id, row.maybe_id))
+                .collect()
+                .await;
+            ids.sort();
+
+            assert_eq!(ids, (0..12).map(|id| (id, Some(id))).collect::<Vec<_>>());
+        }
+
+        fn id_schema() -> ProtoSchema {
+            use prost_types::field_descriptor_proto::{Label, Type};
+            use prost_types::{DescriptorProto, FieldDescriptorProto};
+
+            let id = FieldDescriptorProto {
+                name: Some("id".to_string()),
+                number: Some(1),
+                label: Some(Label::Required as i32),
+                r#type: Some(Type::Int64 as i32),
+                ..Default::default()
+            };
+            ProtoSchema {
+                proto_descriptor: Some(DescriptorProto {
+                    name: Some("Row".to_string()),
+                    field: vec![id],
+                    ..Default::default()
+                }),
+            }
+        }
+
+        fn id_rows(ids: std::ops::Range<i64>) -> Vec<Vec<u8>> {
+            ids.map(|id| {
+                let mut row = Vec::new();
+                prost::encoding::int64::encode(1, &id, &mut row);
+                row
+            })
+            .collect()
+        }
+
+        #[tokio::test]
+        async fn append_rows_to_default_stream() {
+            let server = FakeServer::builder().start().await.unwrap();
+            let client = server.client().await.unwrap();
+
+            let mut writer = client.default_stream_writer(&test_table(), id_schema());
+            assert!(writer
+                .write_stream()
+                .ends_with("/tables/fake_table/streams/_default"));
+            for i in 0..2 {
+                let appended = writer.append(id_rows(3 * i..3 * i + 3)).await.unwrap();
+                assert_eq!(appended.offset(), None);
+            }
+
+            client
+                .append_rows(&test_table(), id_schema(), id_rows(6..9))
+                .await
+                .unwrap();
+
+            assert_eq!(server.written_rows(&test_table()), id_rows(0..9));
+        }
+
+        #[tokio::test]
+        async fn append_record_batch_to_default_stream() {
+            let server = FakeServer::builder().start().await.unwrap();
+            let client = server.client().await.unwrap();
+
+            let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+            let ids = Int64Array::from(vec![0, 1, 2]);
+            let batch = RecordBatch::try_new(schema, vec![Arc::new(ids)]).unwrap();
+            client
+                .append_record_batch(&test_table(), &batch)
+                .await
+                .unwrap();
+
+            // Encoded like the rows of `id_schema`
+            assert_eq!(server.written_rows(&test_table()), id_rows(0..3));
+        }
+
+        #[tokio::test]
+        async fn commit_pending_streams() {
+            let server = FakeServer::builder().start().await.unwrap();
+            let client = server.client().await.unwrap();
+
+            let write_session = client.write_session(&test_table(), WriteStreamType::Pending);
+            let mut first = write_session.create_stream(id_schema()).await.unwrap();
+            let mut second = write_session.create_stream(id_schema()).await.unwrap();
+
+            let appended = first.append_at(0, id_rows(0..3)).await.unwrap();
+            assert_eq!(appended.offset(), Some(0));
+            first.append_at(3, id_rows(3..6)).await.unwrap();
+            second.append_at(0, id_rows(6..9)).await.unwrap();
+
+            // Retrying an append does not write its rows twice
+            match first.append_at(3, id_rows(3..6)).await {
+                Err(Error::Append(e)) => assert_eq!(e.status().code(), tonic::Code::AlreadyExists),
+                _ => panic!("expected the retried append to be rejected"),
+            }
+            assert!(server.written_rows(&test_table()).is_empty());
+
+            write_session.commit().await.unwrap();
+
+            let mut written = server.written_rows(&test_table());
+            written.sort();
+            let mut expected = id_rows(0..9);
+            expected.sort();
+            assert_eq!(written, expected);
+        }
+
+        #[tokio::test]
+        async fn name_stream_not_finalized() {
+            let server = FakeServer::builder()
+                .fail_finalize_after(2)
+                .start()
+                .await
+                .unwrap();
+            let client = server.client().await.unwrap();
+
+            let write_session = client.write_session(&test_table(), WriteStreamType::Pending);
+            let mut first = write_session.create_stream(id_schema()).await.unwrap();
+            write_session.create_stream(id_schema()).await.unwrap();
+            let third = write_session.create_stream(id_schema()).await.unwrap();
+            first.append_at(0, id_rows(0..3)).await.unwrap();
+            assert_eq!(first.finalize().await.unwrap(), 3);
+
+            // The first stream is not finalized again, so the third one fails
+            match write_session.commit().await {
+                Err(Error::Finalize(e)) => {
+                    assert_eq!(e.stream(), third.write_stream());
+                    assert_eq!(e.error().status().unwrap().code(), tonic::Code::Internal);
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+            assert!(server.written_rows(&test_table()).is_empty());
+        }
+
+        #[tokio::test]
+        async fn flush_buffered_stream() {
+            let server = FakeServer::builder().start().await.unwrap();
+            let client = server.client().await.unwrap();
+
+            let write_session = client.write_session(&test_table(), WriteStreamType::Buffered);
+            let mut writer = write_session.create_stream(id_schema()).await.unwrap();
+            writer.append_at(0, id_rows(0..6)).await.unwrap();
+            assert!(server.written_rows(&test_table()).is_empty());
+
+            assert_eq!(writer.flush(3).await.unwrap(), 3);
+            assert_eq!(server.written_rows(&test_table()), id_rows(0..4));
+
+            assert_eq!(writer.finalize().await.unwrap(), 6);
+            assert!(writer.append_at(6, id_rows(6..9)).await.is_err());
+
+            match write_session.commit().await {
+                Err(Error::Usage(_)) => {}
+                other => panic!("unexpected {:?}", other),
+            }
+        }
     }
 }
@@ -0,0 +1,525 @@
+//! Deserializing the rows of a stream into Rust types with [serde](https://serde.rs).
+//!
+//! Rows are first converted into a format-independent tree of values, so the same
+//! type can be read from Arrow or Avro sessions. Temporal types are deserialized
+//! from their raw representation: days since the epoch for dates, and the number of
+//! units since the epoch (or midnight) for timestamps and times. Decimals are
+//! deserialized from strings. Bytes are deserialized into `Vec<u8>`, or as a byte
+//! buffer, e.g. with [serde_bytes](https://docs.rs/serde_bytes).
+use futures::stream::BoxStream;
+use serde::de::value::SeqDeserializer;
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+};
+
+use crate::error::column_path;
+use crate::Error;
+
+#[cfg(feature = "arrow")]
+use crate::row::{downcast, ColumnError};
+#[cfg(feature = "arrow")]
+use arrow::array::{
+    Array, BinaryArray, BooleanArray, Date32Array, Date64Array, DecimalArray, FixedSizeBinaryArray,
+    Float32Array, Float64Array, Int16Array, Int32Array, Int64Array, Int8Array, LargeBinaryArray,
+    LargeListArray, LargeStringArray, ListArray, StringArray, StructArray, Time32MillisecondArray,
+    Time32SecondArray, Time64MicrosecondArray, Time64NanosecondArray, TimestampMicrosecondArray,
+    TimestampMillisecondArray, TimestampNanosecondArray, TimestampSecondArray, UInt16Array,
+    UInt32Array, UInt64Array, UInt8Array,
+};
+#[cfg(feature = "arrow")]
+use arrow::datatypes::{DataType, TimeUnit};
+#[cfg(feature = "arrow")]
+use arrow::record_batch::RecordBatch;
+
+#[cfg(feature = "avro")]
+use avro_rs::types::Value as AvroValue;
+
+/// A stream of rows deserialized into `T`.
+pub type DeserializeStream<T> = BoxStream<'static, Result<T, Error>>;
+
+/// A row that could not be deserialized.
+#[derive(Debug)]
+pub struct DeserializeError {
+    column: String,
+    message: String,
+}
+
+impl DeserializeError {
+    /// The path of the column that could not be deserialized, e.g. `address.city` or
+    /// `tags[2]`. Empty if the error is about the row itself.
+    pub fn column(&self) -> &str {
+        &self.column
+    }
+
+    /// Prefix the column of this error with `segment`, a field name or a `[index]`.
+    fn in_column(mut self, segment: &str) -> Self {
+        self.column = column_path(segment, &self.column);
+        self
+    }
+}
+
+impl std::fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.column.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "column `{}`: {}", self.column, self.message)
+        }
+    }
+}
+
+impl std::error::Error for DeserializeError {}
+
+#[cfg(feature = "arrow")]
+impl From<ColumnError> for DeserializeError {
+    fn from(e: ColumnError) -> Self {
+        Self {
+            column: e.column,
+            message: e.message,
+        }
+    }
+}
+
+impl de::Error for DeserializeError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self {
+            column: String::new(),
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// A value of a row, independent of the format it was read from.
+#[derive(Clone, Debug, PartialEq)]
+enum Cell {
+    Null,
+    Bool(bool),
+    Int(i64),
+    /// Only Arrow has unsigned integers.
+    #[cfg(feature = "arrow")]
+    UInt(u64),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    List(Vec<Cell>),
+    Record(Vec<(String, Cell)>),
+}
+
+/// Deserialize a single row.
+fn from_cell<T: DeserializeOwned>(row: Cell) -> Result<T, Error> {
+    Ok(T::deserialize(CellDeserializer(row))?)
+}
+
+/// Deserialize the rows of `batch`. Each row is deserialized on its own, so an
+/// invalid row does not prevent reading the others.
+#[cfg(feature = "arrow")]
+pub(crate) fn from_record_batch<T: DeserializeOwned>(batch: &RecordBatch) -> Vec<Result<T, Error>> {
+    let schema = batch.schema();
+    (0..batch.num_rows())
+        .map(|row| {
+            let mut record = Vec::with_capacity(batch.num_columns());
+            for (field, column) in schema.fields().iter().zip(batch.columns()) {
+                let cell =
+                    arrow_cell(column.as_ref(), row).map_err(|e| e.in_column(field.name()))?;
+                record.push((field.name().clone(), cell));
+            }
+            from_cell(Cell::Record(record))
+        })
+        .collect()
+}
+
+/// Deserialize a row read from an Avro session.
+#[cfg(feature = "avro")]
+pub(crate) fn from_avro_value<T: DeserializeOwned>(value: AvroValue) -> Result<T, Error> {
+    from_cell(avro_cell(value)?)
+}
+
+#[cfg(feature = "arrow")]
+fn unsupported_arrow(data_type: &DataType) -> DeserializeError {
+    de::Error::custom(format!("cannot deserialize Arrow {:?}", data_type))
+}
+
+#[cfg(feature = "arrow")]
+fn arrow_list(values: &dyn Array) -> Result<Cell, DeserializeError> {
+    let items = (0..values.len())
+        .map(|i| arrow_cell(values, i).map_err(|e| e.in_column(&format!("[{}]", i))))
+        .collect::<Result<_, _>>()?;
+    Ok(Cell::List(items))
+}
+
+#[cfg(feature = "arrow")]
+fn arrow_cell(array: &dyn Array, row: usize) -> Result<Cell, DeserializeError> {
+    if array.is_null(row) {
+        return Ok(Cell::Null);
+    }
+
+    let cell = match array.data_type() {
+        DataType::Null => Cell::Null,
+        DataType::Boolean => Cell::Bool(downcast::<BooleanArray>(array)?.value(row)),
+        DataType::Int8 => Cell::Int(downcast::<Int8Array>(array)?.value(row) as i64),
+        DataType::Int16 => Cell::Int(downcast::<Int16Array>(array)?.value(row) as i64),
+        DataType::Int32 => Cell::Int(downcast::<Int32Array>(array)?.value(row) as i64),
+        DataType::Int64 => Cell::Int(downcast::<Int64Array>(array)?.value(row)),
+        DataType::UInt8 => Cell::UInt(downcast::<UInt8Array>(array)?.value(row) as u64),
+        DataType::UInt16 => Cell::UInt(downcast::<UInt16Array>(array)?.value(row) as u64),
+        DataType::UInt32 => Cell::UInt(downcast::<UInt32Array>(array)?.value(row) as u64),
+        DataType::UInt64 => Cell::UInt(downcast::<UInt64Array>(array)?.value(row)),
+        DataType::Float32 => Cell::Float(downcast::<Float32Array>(array)?.value(row) as f64),
+        DataType::Float64 => Cell::Float(downcast::<Float64Array>(array)?.value(row)),
+        DataType::Utf8 => Cell::String(downcast::<StringArray>(array)?.value(row).to_string()),
+        DataType::LargeUtf8 => {
+            Cell::String(downcast::<LargeStringArray>(array)?.value(row).to_string())
+        }
+        DataType::Binary => Cell::Bytes(downcast::<BinaryArray>(array)?.value(row).to_vec()),
+        DataType::LargeBinary => {
+            Cell::Bytes(downcast::<LargeBinaryArray>(array)?.value(row).to_vec())
+        }
+        DataType::FixedSizeBinary(_) => {
+            Cell::Bytes(downcast::<FixedSizeBinaryArray>(array)?.value(row).to_vec())
+        }
+        DataType::Date32(_) => Cell::Int(downcast::<Date32Array>(array)?.value(row) as i64),
+        DataType::Date64(_) => Cell::Int(downcast::<Date64Array>(array)?.value(row)),
+        DataType::Timestamp(TimeUnit::Second, _) => {
+            Cell::Int(downcast::<TimestampSecondArray>(array)?.value(row))
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, _) => {
+            Cell::Int(downcast::<TimestampMillisecondArray>(array)?.value(row))
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            Cell::Int(downcast::<TimestampMicrosecondArray>(array)?.value(row))
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+            Cell::Int(downcast::<TimestampNanosecondArray>(array)?.value(row))
+        }
+        DataType::Time32(TimeUnit::Second) => {
+            Cell::Int(downcast::<Time32SecondArray>(array)?.value(row) as i64)
+        }
+        DataType::Time32(TimeUnit::Millisecond) => {
+            Cell::Int(downcast::<Time32MillisecondArray>(array)?.value(row) as i64)
+        }
+        DataType::Time64(TimeUnit::Microsecond) => {
+            Cell::Int(downcast::<Time64MicrosecondArray>(array)?.value(row))
+        }
+        DataType::Time64(TimeUnit::Nanosecond) => {
+            Cell::Int(downcast::<Time64NanosecondArray>(array)?.value(row))
+        }
+        DataType::Decimal(_, scale) => {
+            let value = downcast::<DecimalArray>(array)?.value(row);
+            Cell::String(format_decimal(value, *scale))
+        }
+        DataType::List(_) => arrow_list(downcast::<ListArray>(array)?.value(row).as_ref())?,
+        DataType::LargeList(_) => {
+            arrow_list(downcast::<LargeListArray>(array)?.value(row).as_ref())?
+        }
+        DataType::Struct(fields) => {
+            let struct_array = downcast::<StructArray>(array)?;
+            let mut record = Vec::with_capacity(fields.len());
+            for (field, column) in fields.iter().zip(struct_array.columns()) {
+                let cell =
+                    arrow_cell(column.as_ref(), row).map_err(|e| e.in_column(field.name()))?;
+                record.push((field.name().clone(), cell));
+            }
+            Cell::Record(record)
+        }
+        other => return Err(unsupported_arrow(other)),
+    };
+    Ok(cell)
+}
+
+/// Format the unscaled `value` of a decimal with `scale` digits after the point.
+#[cfg(feature = "arrow")]
+fn format_decimal(value: i128, scale: usize) -> String {
+    let sign = if value < 0 { "-" } else { "" };
+    let digits = value.unsigned_abs().to_string();
+    if scale == 0 {
+        return format!("{}{}", sign, digits);
+    }
+
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - scale);
+    format!("{}{}.{}", sign, integer, fraction)
+}
+
+#[cfg(feature = "avro")]
+fn avro_cell(value: AvroValue) -> Result<Cell, DeserializeError> {
+    let cell = match value {
+        AvroValue::Null => Cell::Null,
+        AvroValue::Boolean(b) => Cell::Bool(b),
+        AvroValue::Int(i) | AvroValue::Date(i) | AvroValue::TimeMillis(i) => Cell::Int(i as i64),
+        AvroValue::Long(i)
+        | AvroValue::TimeMicros(i)
+        | AvroValue::TimestampMillis(i)
+        | AvroValue::TimestampMicros(i) => Cell::Int(i),
+        AvroValue::Float(f) => Cell::Float(f as f64),
+        AvroValue::Double(f) => Cell::Float(f),
+        AvroValue::Bytes(bytes) | AvroValue::Fixed(_, bytes) => Cell::Bytes(bytes),
+        AvroValue::String(s) | AvroValue::Enum(_, s) => Cell::String(s),
+        AvroValue::Uuid(uuid) => Cell::String(uuid.to_string()),
+        AvroValue::Union(value) => avro_cell(*value)?,
+        AvroValue::Array(values) => {
+            let mut items = Vec::with_capacity(values.len());
+            for (i, value) in values.into_iter().enumerate() {
+                items.push(avro_cell(value).map_err(|e| e.in_column(&format!("[{}]", i)))?);
+            }
+            Cell::List(items)
+        }
+        AvroValue::Map(values) => {
+            let mut record = Vec::with_capacity(values.len());
+            for (key, value) in values.into_iter() {
+                let cell = avro_cell(value).map_err(|e| e.in_column(&key))?;
+                record.push((key, cell));
+            }
+            Cell::Record(record)
+        }
+        AvroValue::Record(fields) => {
+            let mut record = Vec::with_capacity(fields.len());
+            for (name, value) in fields.into_iter() {
+                let cell = avro_cell(value).map_err(|e| e.in_column(&name))?;
+                record.push((name, cell));
+            }
+            Cell::Record(record)
+        }
+        other => {
+            return Err(de::Error::custom(format!(
+                "cannot deserialize Avro {:?}",
+                other
+            )))
+        }
+    };
+    Ok(cell)
+}
+
+struct CellDeserializer(Cell);
+
+impl<'de> de::Deserializer<'de> for CellDeserializer {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Cell::Null => visitor.visit_unit(),
+            Cell::Bool(b) => visitor.visit_bool(b),
+            Cell::Int(i) => visitor.visit_i64(i),
+            #[cfg(feature = "arrow")]
+            Cell::UInt(u) => visitor.visit_u64(u),
+            Cell::Float(f) => visitor.visit_f64(f),
+            Cell::String(s) => visitor.visit_string(s),
+            Cell::Bytes(bytes) => visitor.visit_byte_buf(bytes),
+            Cell::List(items) => visitor.visit_seq(ListAccess {
+                items: items.into_iter().enumerate(),
+            }),
+            Cell::Record(fields) => visitor.visit_map(RecordAccess {
+                fields: fields.into_iter(),
+                value: None,
+            }),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Cell::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            // `Vec<u8>` expects a sequence, not a byte buffer
+            Cell::Bytes(bytes) => {
+                de::Deserializer::deserialize_seq(SeqDeserializer::new(bytes.into_iter()), visitor)
+            }
+            cell => CellDeserializer(cell).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Cell::String(s) => visitor.visit_enum(s.into_deserializer()),
+            _ => Err(de::Error::custom(format!(
+                "expected a variant of {} as a string",
+                name
+            ))),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string
+        bytes byte_buf unit unit_struct tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+struct ListAccess {
+    items: std::iter::Enumerate<std::vec::IntoIter<Cell>>,
+}
+
+impl<'de> SeqAccess<'de> for ListAccess {
+    type Error = DeserializeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        match self.items.next() {
+            Some((i, item)) => seed
+                .deserialize(CellDeserializer(item))
+                .map(Some)
+                .map_err(|e| e.in_column(&format!("[{}]", i))),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct RecordAccess {
+    fields: std::vec::IntoIter<(String, Cell)>,
+    value: Option<(String, Cell)>,
+}
+
+impl<'de> MapAccess<'de> for RecordAccess {
+    type Error = DeserializeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        match self.fields.next() {
+            Some((name, cell)) => {
+                let key = IntoDeserializer::<DeserializeError>::into_deserializer(name.as_str());
+                let key = seed.deserialize(key)?;
+                self.value = Some((name, cell));
+                Ok(Some(key))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let (name, cell) = self.value.take().ok_or_else(|| {
+            <DeserializeError as de::Error>::custom("value requested before its key")
+        })?;
+        seed.deserialize(CellDeserializer(cell))
+            .map_err(|e| e.in_column(&name))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.fields.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Address {
+        city: String,
+        zip: Option<i32>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Row {
+        id: i64,
+        tags: Vec<String>,
+        address: Address,
+    }
+
+    fn row(zip: Cell, tags: Vec<Cell>) -> Cell {
+        Cell::Record(vec![
+            ("id".to_string(), Cell::Int(1)),
+            ("tags".to_string(), Cell::List(tags)),
+            (
+                "address".to_string(),
+                Cell::Record(vec![
+                    ("city".to_string(), Cell::String("London".to_string())),
+                    ("zip".to_string(), zip),
+                ]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn deserialize_nested_row() {
+        let tags = vec![Cell::String("a".to_string())];
+        let row: Row = from_cell(row(Cell::Null, tags)).unwrap();
+        assert_eq!(
+            row,
+            Row {
+                id: 1,
+                tags: vec!["a".to_string()],
+                address: Address {
+                    city: "London".to_string(),
+                    zip: None,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn errors_name_the_column() {
+        let tags = vec![Cell::String("a".to_string())];
+        match from_cell::<Row>(row(Cell::String("N1".to_string()), tags)) {
+            Err(Error::Deserialize(e)) => assert_eq!(e.column(), "address.zip"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let tags = vec![Cell::String("a".to_string()), Cell::Int(2)];
+        match from_cell::<Row>(row(Cell::Null, tags)) {
+            Err(Error::Deserialize(e)) => assert_eq!(e.column(), "tags[1]"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deserialize_bytes_into_vec() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Blob {
+            data: Vec<u8>,
+            parts: Vec<Vec<u8>>,
+        }
+
+        let cell = Cell::Record(vec![
+            ("data".to_string(), Cell::Bytes(vec![1, 2, 3])),
+            (
+                "parts".to_string(),
+                Cell::List(vec![Cell::Bytes(vec![4]), Cell::Bytes(vec![])]),
+            ),
+        ]);
+        let blob: Blob = from_cell(cell).unwrap();
+        assert_eq!(
+            blob,
+            Blob {
+                data: vec![1, 2, 3],
+                parts: vec![vec![4], vec![]],
+            }
+        );
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn format_decimals() {
+        assert_eq!(format_decimal(12345, 2), "123.45");
+        assert_eq!(format_decimal(-5, 3), "-0.005");
+        assert_eq!(format_decimal(42, 0), "42");
+    }
+}
@@ -0,0 +1,350 @@
+//! Classifying the errors of this crate.
+//!
+//! Errors returned by the BigQuery Storage API carry a gRPC status, with
+//! [`google.rpc` details](https://cloud.google.com/apis/design/errors#error_details) such as
+//! the delay after which a call may be retried. [`Error::kind`](crate::Error::kind) sorts
+//! errors into broad classes, and [`Error::details`](crate::Error::details) decodes those
+//! details.
+//! ```rust
+//! # fn f(error: bigquery_storage::Error) {
+//! use bigquery_storage::ErrorKind;
+//!
+//! match error.kind() {
+//!     ErrorKind::NotFound => println!("no such table"),
+//!     ErrorKind::ResourceExhausted => println!("retry in {:?}", error.retry_delay()),
+//!     _ if error.is_retryable() => println!("try again"),
+//!     _ => println!("{}", error),
+//! }
+//! # }
+//! ```
+use prost::Message;
+use tonic::{Code, Status};
+
+use std::time::Duration;
+
+use crate::googleapis::google::rpc::{
+    BadRequest, ErrorInfo, QuotaFailure, RetryInfo, Status as RpcStatus,
+};
+use crate::Error;
+
+/// A broad class of [`Error`](crate::Error).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The table, session or stream does not exist.
+    NotFound,
+    /// The caller is not allowed to read or write the table.
+    PermissionDenied,
+    /// The credentials are missing, invalid or could not be obtained.
+    Unauthenticated,
+    /// A quota or rate limit was exceeded, see [`Error::retry_delay`](crate::Error::retry_delay).
+    ResourceExhausted,
+    /// The request is invalid, e.g. a field or a row restriction does not exist.
+    InvalidArgument,
+    /// What the request creates already exists, e.g. rows were already appended at
+    /// the offset given to [`StreamWriter::append_at`](crate::write::StreamWriter::append_at).
+    AlreadyExists,
+    /// The read session has expired, and must be created again.
+    SessionExpired,
+    /// The state of the table or stream does not allow the request, e.g. the stream
+    /// was already finalized.
+    FailedPrecondition,
+    /// A transient failure, after which the request can be retried.
+    Transient,
+    /// The call did not complete before its deadline.
+    DeadlineExceeded,
+    /// The call was cancelled.
+    Cancelled,
+    /// Any other error, e.g. data that could not be decoded.
+    Other,
+}
+
+/// A call that this crate does not allow, e.g. committing streams that are not `PENDING`.
+#[derive(Debug)]
+pub struct UsageError {
+    message: String,
+}
+
+impl UsageError {
+    pub(crate) fn new<S: Into<String>>(message: S) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for UsageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+/// Prefix the path of a column within a row, e.g. `city` or `[2]`, with `segment`, a
+/// field name or a `[index]`. Empty paths refer to the row itself.
+#[cfg(any(feature = "arrow", feature = "serde"))]
+pub(crate) fn column_path(segment: &str, column: &str) -> String {
+    match column {
+        "" => segment.to_string(),
+        column if column.starts_with('[') => format!("{}{}", segment, column),
+        column => format!("{}.{}", segment, column),
+    }
+}
+
+/// The `google.rpc` details of the status of a failed call.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ErrorDetails {
+    error_info: Option<ErrorInfo>,
+    retry_info: Option<RetryInfo>,
+    quota_failure: Option<QuotaFailure>,
+    bad_request: Option<BadRequest>,
+}
+
+impl ErrorDetails {
+    /// Decode the details of `status`. Details that cannot be decoded are ignored.
+    pub fn from_status(status: &Status) -> Self {
+        let mut details = Self::default();
+        let rpc_status = match RpcStatus::decode(status.details()) {
+            Ok(rpc_status) => rpc_status,
+            Err(_) => return details,
+        };
+        for any in rpc_status.details {
+            let value = any.value.as_slice();
+            match any.type_url.rsplit('/').next() {
+                Some("google.rpc.ErrorInfo") => {
+                    details.error_info = ErrorInfo::decode(value).ok();
+                }
+                Some("google.rpc.RetryInfo") => {
+                    details.retry_info = RetryInfo::decode(value).ok();
+                }
+                Some("google.rpc.QuotaFailure") => {
+                    details.quota_failure = QuotaFailure::decode(value).ok();
+                }
+                Some("google.rpc.BadRequest") => {
+                    details.bad_request = BadRequest::decode(value).ok();
+                }
+                _ => {}
+            }
+        }
+        details
+    }
+
+    /// The reason, domain and metadata of the error.
+    pub fn error_info(&self) -> Option<&ErrorInfo> {
+        self.error_info.as_ref()
+    }
+
+    /// When the request may be retried.
+    pub fn retry_info(&self) -> Option<&RetryInfo> {
+        self.retry_info.as_ref()
+    }
+
+    /// The quotas that were exceeded.
+    pub fn quota_failure(&self) -> Option<&QuotaFailure> {
+        self.quota_failure.as_ref()
+    }
+
+    /// The fields of the request that are invalid.
+    pub fn bad_request(&self) -> Option<&BadRequest> {
+        self.bad_request.as_ref()
+    }
+
+    /// The delay the server asks to wait for before retrying, from the `RetryInfo`.
+    pub fn retry_delay(&self) -> Option<Duration> {
+        let delay = self.retry_info.as_ref()?.retry_delay.as_ref()?;
+        let seconds = Duration::from_secs(delay.seconds.max(0) as u64);
+        Some(seconds + Duration::from_nanos(delay.nanos.max(0) as u64))
+    }
+}
+
+/// Whether `status` reports that the read session it refers to has expired.
+pub(crate) fn is_session_expired(status: &Status) -> bool {
+    match status.code() {
+        Code::FailedPrecondition | Code::NotFound | Code::InvalidArgument => {
+            let message = status.message().to_ascii_lowercase();
+            message.contains("session") && message.contains("expired")
+        }
+        _ => false,
+    }
+}
+
+fn status_kind(status: &Status) -> ErrorKind {
+    if is_session_expired(status) {
+        return ErrorKind::SessionExpired;
+    }
+    if crate::retry::is_retryable(status) {
+        return ErrorKind::Transient;
+    }
+    match status.code() {
+        Code::NotFound => ErrorKind::NotFound,
+        Code::PermissionDenied => ErrorKind::PermissionDenied,
+        Code::Unauthenticated => ErrorKind::Unauthenticated,
+        Code::ResourceExhausted => ErrorKind::ResourceExhausted,
+        Code::InvalidArgument | Code::OutOfRange => ErrorKind::InvalidArgument,
+        Code::AlreadyExists => ErrorKind::AlreadyExists,
+        Code::FailedPrecondition => ErrorKind::FailedPrecondition,
+        Code::Aborted | Code::Unavailable => ErrorKind::Transient,
+        Code::DeadlineExceeded => ErrorKind::DeadlineExceeded,
+        Code::Cancelled => ErrorKind::Cancelled,
+        _ => ErrorKind::Other,
+    }
+}
+
+/// Only the I/O errors of a connection are transient: others, e.g. a missing file of
+/// credentials, are not fixed by retrying.
+fn io_kind(error: &std::io::Error) -> ErrorKind {
+    use std::io::ErrorKind as IoErrorKind;
+    match error.kind() {
+        IoErrorKind::TimedOut
+        | IoErrorKind::Interrupted
+        | IoErrorKind::WouldBlock
+        | IoErrorKind::ConnectionReset
+        | IoErrorKind::ConnectionAborted
+        | IoErrorKind::ConnectionRefused
+        | IoErrorKind::NotConnected
+        | IoErrorKind::BrokenPipe
+        | IoErrorKind::UnexpectedEof => ErrorKind::Transient,
+        _ => ErrorKind::Other,
+    }
+}
+
+impl Error {
+    /// The gRPC status of the failed call, if this error comes from the API.
+    pub fn status(&self) -> Option<&Status> {
+        match self {
+            Self::Status(status) => Some(status),
+            Self::Append(e) => Some(e.status()),
+            Self::Finalize(e) => e.error().status(),
+            _ => None,
+        }
+    }
+
+    /// The class of this error.
+    pub fn kind(&self) -> ErrorKind {
+        if let Some(status) = self.status() {
+            return status_kind(status);
+        }
+        match self {
+            Self::Transport(_) | Self::Hyper(_) => ErrorKind::Transient,
+            Self::Io(e) => io_kind(e),
+            Self::Timeout(_) => ErrorKind::DeadlineExceeded,
+            Self::Auth(_) | Self::Credentials(_) | Self::MetadataEncoding(_) => {
+                ErrorKind::Unauthenticated
+            }
+            Self::ParseTable(_) => ErrorKind::InvalidArgument,
+            Self::SessionExpired(_) => ErrorKind::SessionExpired,
+            Self::Usage(_) => ErrorKind::FailedPrecondition,
+            Self::Finalize(e) => e.error().kind(),
+            _ => ErrorKind::Other,
+        }
+    }
+
+    /// The `google.rpc` details of the status of the failed call. Empty if this error
+    /// does not come from the API, or if the status has no details.
+    pub fn details(&self) -> ErrorDetails {
+        self.status()
+            .map(ErrorDetails::from_status)
+            .unwrap_or_default()
+    }
+
+    /// Whether the failed call can be retried as is: after a transient failure, or
+    /// after exhausting a quota when the server tells when to retry.
+    pub fn is_retryable(&self) -> bool {
+        match self.kind() {
+            ErrorKind::Transient => true,
+            ErrorKind::ResourceExhausted => self.retry_delay().is_some(),
+            _ => false,
+        }
+    }
+
+    /// The delay to wait for before retrying, as suggested by the server.
+    pub fn retry_delay(&self) -> Option<Duration> {
+        self.details().retry_delay()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bytes::Bytes;
+    use prost_types::Any;
+
+    fn any<M: Message>(type_name: &str, message: &M) -> Any {
+        let mut value = Vec::new();
+        message.encode(&mut value).unwrap();
+        Any {
+            type_url: format!("type.googleapis.com/{}", type_name),
+            value,
+        }
+    }
+
+    fn status_with_details(code: Code, message: &str, details: Vec<Any>) -> Status {
+        let rpc_status = RpcStatus {
+            code: code as i32,
+            message: message.to_string(),
+            details,
+        };
+        let mut encoded = Vec::new();
+        rpc_status.encode(&mut encoded).unwrap();
+        Status::with_details(code, message, Bytes::from(encoded))
+    }
+
+    #[test]
+    fn decode_details() {
+        let retry_info = RetryInfo {
+            retry_delay: Some(prost_types::Duration {
+                seconds: 2,
+                nanos: 500_000_000,
+            }),
+        };
+        let error_info = ErrorInfo {
+            reason: "RATE_LIMIT_EXCEEDED".to_string(),
+            domain: "bigquerystorage.googleapis.com".to_string(),
+            ..Default::default()
+        };
+        let status = status_with_details(
+            Code::ResourceExhausted,
+            "quota exceeded",
+            vec![
+                any("google.rpc.ErrorInfo", &error_info),
+                any("google.rpc.RetryInfo", &retry_info),
+            ],
+        );
+
+        let error = Error::from(status);
+        assert_eq!(error.kind(), ErrorKind::ResourceExhausted);
+        assert!(error.is_retryable());
+        assert_eq!(error.retry_delay(), Some(Duration::from_millis(2500)));
+        assert_eq!(error.details().error_info(), Some(&error_info));
+        assert!(error.details().bad_request().is_none());
+    }
+
+    #[test]
+    fn classify_statuses() {
+        let kind = |status: Status| Error::from(status).kind();
+        assert_eq!(kind(Status::not_found("no table")), ErrorKind::NotFound);
+        assert_eq!(kind(Status::unavailable("try again")), ErrorKind::Transient);
+        assert_eq!(
+            kind(Status::failed_precondition(
+                "read session projects/p/locations/eu/sessions/s has expired"
+            )),
+            ErrorKind::SessionExpired
+        );
+        assert_eq!(
+            kind(Status::already_exists("offset already written")),
+            ErrorKind::AlreadyExists
+        );
+        assert!(!Error::from(Status::resource_exhausted("quota")).is_retryable());
+        assert!(!Error::from(Status::invalid_argument("bad field")).is_retryable());
+
+        let io = |kind: std::io::ErrorKind| Error::from(std::io::Error::from(kind)).kind();
+        assert_eq!(
+            io(std::io::ErrorKind::ConnectionReset),
+            ErrorKind::Transient
+        );
+        assert_eq!(io(std::io::ErrorKind::TimedOut), ErrorKind::Transient);
+        assert_eq!(io(std::io::ErrorKind::NotFound), ErrorKind::Other);
+    }
+}
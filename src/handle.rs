@@ -0,0 +1,403 @@
+//! Portable handles to read sessions and their streams.
+//!
+//! A coordinator can create a [`ReadSession`](crate::client::ReadSession), export it with
+//! [`ReadSession::handle`](crate::client::ReadSession::handle), and send the handles of its
+//! streams to remote workers. Each worker then opens its stream with
+//! [`Client::read_stream`](crate::client::Client::read_stream), without creating a new session.
+//!
+//! Handles can be serialized to bytes (as a protobuf [`ReadSession`](crate::googleapis::ReadSession))
+//! with `to_bytes`, or with [serde](https://docs.rs/serde) when the `serde` feature is enabled.
+use prost::Message;
+use prost_types::Timestamp;
+
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::googleapis::{
+    read_session::{Schema, TableModifiers},
+    ArrowSchema, AvroSchema, DataFormat, ReadSession as BigQueryReadSession, ReadStream,
+};
+use crate::Error;
+
+/// Convert `timestamp`, which may come from untrusted bytes, failing if it is not
+/// valid or not representable as a `SystemTime` on this platform.
+pub(crate) fn timestamp_to_system_time(timestamp: &Timestamp) -> Result<SystemTime, Error> {
+    if !(0..1_000_000_000).contains(&timestamp.nanos) {
+        return Err(Error::invalid(format!(
+            "invalid timestamp nanos {}",
+            timestamp.nanos
+        )));
+    }
+    let nanos = Duration::from_nanos(timestamp.nanos as u64);
+    let seconds = Duration::from_secs(timestamp.seconds.unsigned_abs());
+    let time = if timestamp.seconds >= 0 {
+        UNIX_EPOCH.checked_add(seconds)
+    } else {
+        UNIX_EPOCH.checked_sub(seconds)
+    };
+    time.and_then(|time| time.checked_add(nanos))
+        .ok_or_else(|| Error::invalid(format!("timestamp {}s out of range", timestamp.seconds)))
+}
+
+pub(crate) fn system_time_to_timestamp(time: SystemTime) -> Timestamp {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => Timestamp {
+            seconds: since.as_secs() as i64,
+            nanos: since.subsec_nanos() as i32,
+        },
+        Err(err) => {
+            let before = err.duration();
+            let mut seconds = -(before.as_secs() as i64);
+            let mut nanos = -(before.subsec_nanos() as i32);
+            if nanos < 0 {
+                seconds -= 1;
+                nanos += 1_000_000_000;
+            }
+            Timestamp { seconds, nanos }
+        }
+    }
+}
+
+/// The schema of a read session, as serialized by the API.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SerializedSchema {
+    /// An Arrow IPC schema message.
+    Arrow(Vec<u8>),
+    /// An Avro schema, in JSON.
+    Avro(String),
+}
+
+impl SerializedSchema {
+    /// The data format of the rows of the session.
+    pub fn data_format(&self) -> DataFormat {
+        match self {
+            Self::Arrow(_) => DataFormat::Arrow,
+            Self::Avro(_) => DataFormat::Avro,
+        }
+    }
+}
+
+impl From<SerializedSchema> for Schema {
+    fn from(schema: SerializedSchema) -> Self {
+        match schema {
+            SerializedSchema::Arrow(serialized_schema) => {
+                Schema::ArrowSchema(ArrowSchema { serialized_schema })
+            }
+            SerializedSchema::Avro(schema) => Schema::AvroSchema(AvroSchema { schema }),
+        }
+    }
+}
+
+impl From<Schema> for SerializedSchema {
+    fn from(schema: Schema) -> Self {
+        match schema {
+            Schema::ArrowSchema(ArrowSchema { serialized_schema }) => {
+                SerializedSchema::Arrow(serialized_schema)
+            }
+            Schema::AvroSchema(AvroSchema { schema }) => SerializedSchema::Avro(schema),
+        }
+    }
+}
+
+/// A portable description of a [`ReadSession`](crate::client::ReadSession): its name,
+/// table, schema, snapshot and expiration times and the names of its streams.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReadSessionHandle {
+    name: String,
+    table: String,
+    schema: SerializedSchema,
+    streams: Vec<String>,
+    snapshot_time: Option<SystemTime>,
+    expire_time: Option<SystemTime>,
+}
+
+impl ReadSessionHandle {
+    /// The name of the session, of the form
+    /// `projects/{project_id}/locations/{location}/sessions/{session_id}`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The table read by the session, of the form
+    /// `projects/{project_id}/datasets/{dataset_id}/tables/{table_id}`.
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    /// The schema of the rows of the session.
+    pub fn schema(&self) -> &SerializedSchema {
+        &self.schema
+    }
+
+    /// The data format of the rows of the session.
+    pub fn data_format(&self) -> DataFormat {
+        self.schema.data_format()
+    }
+
+    /// The names of the streams of the session.
+    pub fn streams(&self) -> &[String] {
+        &self.streams
+    }
+
+    /// The snapshot time the session reads the table at, if known, see
+    /// [`ReadSession::snapshot_time`](crate::client::ReadSession::snapshot_time).
+    pub fn snapshot_time(&self) -> Option<SystemTime> {
+        self.snapshot_time
+    }
+
+    /// The time after which the session, and all of its streams, can no longer be read.
+    pub fn expire_time(&self) -> Option<SystemTime> {
+        self.expire_time
+    }
+
+    /// One handle for each stream of the session, to be handed out to workers.
+    pub fn stream_handles(&self) -> Vec<ReadStreamHandle> {
+        self.streams
+            .iter()
+            .map(|stream| ReadStreamHandle {
+                session: self.name.clone(),
+                table: self.table.clone(),
+                schema: self.schema.clone(),
+                stream: stream.clone(),
+                snapshot_time: self.snapshot_time,
+                expire_time: self.expire_time,
+            })
+            .collect()
+    }
+
+    /// Serialize the handle, as a protobuf [`ReadSession`](crate::googleapis::ReadSession).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode(&self.to_session())
+    }
+
+    /// Deserialize a handle serialized with [`to_bytes`](ReadSessionHandle::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Self::try_from(BigQueryReadSession::decode(bytes)?)
+    }
+
+    pub(crate) fn to_session(&self) -> BigQueryReadSession {
+        session(
+            &self.name,
+            &self.table,
+            &self.schema,
+            &self.streams,
+            self.snapshot_time,
+            self.expire_time,
+        )
+    }
+}
+
+impl TryFrom<BigQueryReadSession> for ReadSessionHandle {
+    type Error = Error;
+
+    fn try_from(session: BigQueryReadSession) -> Result<Self, Error> {
+        let schema: SerializedSchema = session
+            .schema
+            .ok_or(Error::invalid("empty schema response"))?
+            .into();
+        let snapshot_time = session
+            .table_modifiers
+            .and_then(|table_modifiers| table_modifiers.snapshot_time)
+            .as_ref()
+            .map(timestamp_to_system_time)
+            .transpose()?;
+        let expire_time = session
+            .expire_time
+            .as_ref()
+            .map(timestamp_to_system_time)
+            .transpose()?;
+        Ok(Self {
+            name: session.name,
+            table: session.table,
+            schema,
+            streams: session.streams.into_iter().map(|s| s.name).collect(),
+            snapshot_time,
+            expire_time,
+        })
+    }
+}
+
+/// A portable description of a single stream of a read session, sufficient to
+/// read it with [`Client::read_stream`](crate::client::Client::read_stream).
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReadStreamHandle {
+    session: String,
+    table: String,
+    schema: SerializedSchema,
+    stream: String,
+    snapshot_time: Option<SystemTime>,
+    expire_time: Option<SystemTime>,
+}
+
+impl ReadStreamHandle {
+    /// The name of the session the stream belongs to.
+    pub fn session(&self) -> &str {
+        &self.session
+    }
+
+    /// The table read by the session.
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    /// The schema of the rows of the stream.
+    pub fn schema(&self) -> &SerializedSchema {
+        &self.schema
+    }
+
+    /// The name of the stream, of the form
+    /// `projects/{project_id}/locations/{location}/sessions/{session_id}/streams/{stream_id}`.
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
+
+    /// The snapshot time the session reads the table at, if known.
+    pub fn snapshot_time(&self) -> Option<SystemTime> {
+        self.snapshot_time
+    }
+
+    /// The time after which the stream can no longer be read.
+    pub fn expire_time(&self) -> Option<SystemTime> {
+        self.expire_time
+    }
+
+    /// Serialize the handle, as a protobuf [`ReadSession`](crate::googleapis::ReadSession)
+    /// with a single stream.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode(&self.to_session())
+    }
+
+    /// Deserialize a handle serialized with [`to_bytes`](ReadStreamHandle::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut handle = ReadSessionHandle::from_bytes(bytes)?;
+        let stream = match handle.streams.pop() {
+            Some(stream) if handle.streams.is_empty() => stream,
+            _ => return Err(Error::invalid("expected a session with a single stream")),
+        };
+        Ok(Self {
+            session: handle.name,
+            table: handle.table,
+            schema: handle.schema,
+            stream,
+            snapshot_time: handle.snapshot_time,
+            expire_time: handle.expire_time,
+        })
+    }
+
+    pub(crate) fn to_session(&self) -> BigQueryReadSession {
+        session(
+            &self.session,
+            &self.table,
+            &self.schema,
+            std::slice::from_ref(&self.stream),
+            self.snapshot_time,
+            self.expire_time,
+        )
+    }
+}
+
+fn session(
+    name: &str,
+    table: &str,
+    schema: &SerializedSchema,
+    streams: &[String],
+    snapshot_time: Option<SystemTime>,
+    expire_time: Option<SystemTime>,
+) -> BigQueryReadSession {
+    let mut session = BigQueryReadSession {
+        name: name.to_string(),
+        table: table.to_string(),
+        schema: Some(schema.clone().into()),
+        streams: streams
+            .iter()
+            .map(|name| ReadStream { name: name.clone() })
+            .collect(),
+        table_modifiers: snapshot_time.map(|snapshot_time| TableModifiers {
+            snapshot_time: Some(system_time_to_timestamp(snapshot_time)),
+        }),
+        expire_time: expire_time.map(system_time_to_timestamp),
+        ..Default::default()
+    };
+    session.set_data_format(schema.data_format());
+    session
+}
+
+fn encode<M: Message>(msg: &M) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(msg.encoded_len());
+    // Encoding into a `Vec` only fails if it cannot grow
+    msg.encode(&mut buf).expect("insufficient buffer capacity");
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handles_roundtrip_through_bytes() {
+        let session = BigQueryReadSession {
+            name: "projects/p/locations/us/sessions/s".to_string(),
+            table: "projects/p/datasets/d/tables/t".to_string(),
+            schema: Some(Schema::AvroSchema(AvroSchema {
+                schema: r#"{"type": "record", "name": "t", "fields": []}"#.to_string(),
+            })),
+            streams: vec![
+                ReadStream {
+                    name: "projects/p/locations/us/sessions/s/streams/a".to_string(),
+                },
+                ReadStream {
+                    name: "projects/p/locations/us/sessions/s/streams/b".to_string(),
+                },
+            ],
+            table_modifiers: Some(TableModifiers {
+                snapshot_time: Some(Timestamp {
+                    seconds: 1_599_990_000,
+                    nanos: 0,
+                }),
+            }),
+            expire_time: Some(Timestamp {
+                seconds: 1_600_000_000,
+                nanos: 500,
+            }),
+            ..Default::default()
+        };
+
+        let handle = ReadSessionHandle::try_from(session.clone()).unwrap();
+        assert_eq!(handle.data_format(), DataFormat::Avro);
+        assert_eq!(
+            handle.snapshot_time(),
+            Some(UNIX_EPOCH + Duration::from_secs(1_599_990_000))
+        );
+
+        let decoded = ReadSessionHandle::from_bytes(&handle.to_bytes()).unwrap();
+        assert_eq!(decoded, handle);
+
+        let stream_handles = handle.stream_handles();
+        assert_eq!(stream_handles.len(), 2);
+        let decoded = ReadStreamHandle::from_bytes(&stream_handles[1].to_bytes()).unwrap();
+        assert_eq!(decoded, stream_handles[1]);
+        assert_eq!(
+            decoded.stream(),
+            "projects/p/locations/us/sessions/s/streams/b"
+        );
+        assert_eq!(
+            decoded.to_session().table_modifiers,
+            session.table_modifiers
+        );
+    }
+
+    #[test]
+    fn reject_invalid_timestamps() {
+        for nanos in [-1, 1_000_000_000] {
+            let session = BigQueryReadSession {
+                schema: Some(Schema::AvroSchema(AvroSchema::default())),
+                expire_time: Some(Timestamp { seconds: 0, nanos }),
+                ..Default::default()
+            };
+            assert!(ReadSessionHandle::from_bytes(&encode(&session)).is_err());
+        }
+    }
+}
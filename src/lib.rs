@@ -1,15 +1,25 @@
 //! # bigquery-storage
 //! A small wrapper around the [Google BigQuery Storage API](https://cloud.google.com/bigquery/docs/reference/storage).
 //!
-//! The BigQuery Storage API allows reading BigQuery tables by serializing their contents into efficient, concurrent streams. The official API supports both binary serialized Arrow and AVRO formats, but this crate only supports outputting Arrow [RecordBatch](arrow::record_batch::RecordBatch) at the moment.
+//! The BigQuery Storage API allows reading BigQuery tables by serializing their contents into efficient, concurrent streams. The official API supports both binary serialized Arrow and AVRO formats. This crate outputs Arrow [RecordBatch](arrow::record_batch::RecordBatch) with the `arrow` feature (enabled by default), and Avro [values](https://docs.rs/avro-rs/*/avro_rs/types/enum.Value.html) with the `avro` feature.
 //! # Usage
-//! 0. You will need some form of authentication, provided by an [`Authenticator`](yup_oauth2::authenticator::Authenticator).
+//! 0. You will need some form of authentication, provided by a [`TokenProvider`](crate::auth::TokenProvider) such as a yup_oauth2 [`Authenticator`](yup_oauth2::authenticator::Authenticator).
 //! 1. You will first need to create a [`Client`](crate::client::Client), with [`Client::new`](crate::client::Client::new).
 //! 2. Reading tables is done in [read sessions](https://cloud.google.com/bigquery/docs/reference/storage#create_a_session). In this crate, this is handled by [`Client::read_session_builder`](crate::client::Client::read_session_builder).
-//! 3. After that you will have a [`ReadSession`](crate::client::ReadSession), which is a small wrapper around a collection of [read streams](https://cloud.google.com/bigquery/docs/reference/storage#read_from_a_session_stream). Go through the streams with [`ReadSession::next_stream`](crate::client::ReadSession::next_stream).
-//! 4. Each storage stream is wrapped in a [`RowsStreamReader`](crate::read::RowsStreamReader). This will let you consume the stream into an Arrow [`StreamReader`](arrow::ipc::reader::StreamReader), at which point the data will actually be downloaded.
+//! 3. After that you will have a [`ReadSession`](crate::client::ReadSession), which is a small wrapper around a collection of [read streams](https://cloud.google.com/bigquery/docs/reference/storage#read_from_a_session_stream). Go through the streams with [`ReadSession::next_stream`](crate::client::ReadSession::next_stream), or read them all concurrently with [`ReadSession::parallel_reader`](crate::client::ReadSession::parallel_reader).
+//! 4. Each storage stream is wrapped in a [`RowsStreamReader`](crate::read::RowsStreamReader). This will let you consume the stream into an Arrow [`StreamReader`](arrow::ipc::reader::StreamReader), at which point the data will actually be downloaded. To process record batches as they arrive instead of buffering the whole stream, use [`RowsStreamReader::into_record_batch_stream`](crate::read::RowsStreamReader::into_record_batch_stream).
+//!
+//! With the `serde` feature, rows can be deserialized into your own types with [`RowsStreamReader::into_deserialize`](crate::read::RowsStreamReader::into_deserialize).
+//!
+//! With the `derive` feature, `#[derive(BigQueryRow)]` generates both the selected fields and the decoding of a struct, read with [`ReadSessionBuilder::read_as`](crate::client::ReadSessionBuilder::read_as). See the [`row`](crate::row) module.
+//!
+//! Several tables can be read at a consistent snapshot with [`ReadSessionBuilder::as_of_now`](crate::client::ReadSessionBuilder::as_of_now) and [`ReadSessionBuilder::same_snapshot_as`](crate::client::ReadSessionBuilder::same_snapshot_as). With the `chrono` feature, snapshot times can also be given as `chrono::DateTime`s.
+//!
+//! With the `tracing` feature, calls to the API and the decoding of messages are wrapped in [`tracing`](https://docs.rs/tracing) spans. Metrics such as the latency of calls and the bytes and rows received can be exported with a [`Metrics`](crate::metrics::Metrics) hook.
+//!
+//! Tables can also be loaded through the [Storage Write API](https://cloud.google.com/bigquery/docs/write-api), see the [`write`](crate::write) module and [`Client::append_rows`](crate::client::Client::append_rows).
 //! # Example
-//! ```rust
+//! ```rust,no_run
 //! use bigquery_storage::{Table, Client};
 //!
 //! #[tokio::main(flavor = "current_thread")]
@@ -24,7 +34,7 @@
 //!         .await?;
 //!
 //!     // 3. Create a Client
-//!     let mut client = Client::new(auth).await?;
+//!     let client = Client::new(auth).await?;
 //!
 //!     // Reading the content of a table `bigquery-public-beta:london_bicycles.cycle_stations`
 //!     let test_table = Table::new(
@@ -35,7 +45,7 @@
 //!
 //!     // Create a new ReadSession; the `parent_project_id` is the ID of the GCP project
 //!     // that owns the read job. This does not download any data.
-//!     let mut read_session = client
+//!     let read_session = client
 //!         .read_session_builder(test_table)
 //!         .parent_project_id("openquery-public-testing".to_string())
 //!         .build()
@@ -61,20 +71,106 @@
 //! }
 //! ```
 //! # Authentication
-//! For authentication you need an [Authenticator](yup_oauth2::authenticator::Authenticator), which is provided by the [yup_oauth2](yup_oauth2) crate.
+//! For authentication you need a [`TokenProvider`](crate::auth::TokenProvider). It is implemented for the [Authenticator](yup_oauth2::authenticator::Authenticator) of the [yup_oauth2](yup_oauth2) crate, and the [`auth`](crate::auth) module provides a [`StaticToken`](crate::auth::StaticToken), the GCE [`MetadataServer`](crate::auth::MetadataServer) and workload identity federation with an [`ExternalAccount`](crate::auth::ExternalAccount).
+//! # Errors
+//! All fallible calls return an [`Error`](crate::Error). Use [`Error::kind`](crate::Error::kind) to tell a missing table from an exhausted quota, and [`Error::is_retryable`](crate::Error::is_retryable) and [`Error::retry_delay`](crate::Error::retry_delay) to decide whether to try again.
+//!
+//! Transient failures are retried according to the [`RetryPolicy`](crate::retry::RetryPolicy) of the client. How long calls and reads may take is bounded with [`Client::with_timeout`](crate::client::Client::with_timeout) and [`Client::with_idle_timeout`](crate::client::Client::with_idle_timeout), and they can be aborted with a [`CancellationToken`](crate::CancellationToken), see [`Client::with_cancellation`](crate::client::Client::with_cancellation).
+// `Error` wraps `tonic::Status` by value, so that callers can match on it
+#![allow(clippy::result_large_err)]
+// Without a data format, rows cannot be decoded and most of the read path is unused
+#![cfg_attr(
+    not(any(feature = "arrow", feature = "avro")),
+    allow(dead_code, unused_imports)
+)]
+
 pub use yup_oauth2;
 
+#[cfg(feature = "arrow")]
+pub use arrow;
+
+pub use tokio_util::sync::CancellationToken;
+
+#[cfg(feature = "derive")]
+pub use bigquery_storage_derive::BigQueryRow;
+
+// The derive macro refers to `::bigquery_storage`, including in this crate's tests
+#[cfg(all(test, feature = "derive"))]
+extern crate self as bigquery_storage;
+
 pub mod googleapis {
     //! Codegenerated from [`google.cloud.bigquery.storage.v1`](https://github.com/googleapis/googleapis/tree/master/google/cloud/bigquery/storage/v1).
-    tonic::include_proto!("google.cloud.bigquery.storage.v1");
+    //! The generated code refers to `google.rpc` by relative paths, so packages are
+    //! nested the same way as in the proto files and re-exported here.
+    pub mod google {
+        pub mod rpc {
+            tonic::include_proto!("google.rpc");
+        }
+
+        pub mod cloud {
+            pub mod bigquery {
+                pub mod storage {
+                    pub mod v1 {
+                        tonic::include_proto!("google.cloud.bigquery.storage.v1");
+                    }
+                }
+            }
+        }
+    }
+
+    pub use google::cloud::bigquery::storage::v1::*;
 }
 
+pub mod auth;
+pub use auth::*;
+
+pub mod error;
+pub use error::*;
+
 pub mod client;
 pub use client::*;
 
 pub mod read;
 pub use read::*;
 
+pub mod restriction;
+pub use restriction::*;
+
+#[cfg(feature = "serde")]
+pub mod de;
+#[cfg(feature = "serde")]
+pub use de::*;
+
+#[cfg(feature = "arrow")]
+pub mod row;
+#[cfg(feature = "arrow")]
+pub use row::*;
+
+pub mod parallel;
+pub use parallel::*;
+
+pub mod handle;
+pub use handle::*;
+
+pub mod schema;
+pub use schema::*;
+
+pub mod write;
+pub use write::*;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+pub mod retry;
+pub use retry::*;
+
+mod timeout;
+
+pub mod metrics;
+pub use metrics::*;
+
+mod trace;
+
 macro_rules! errors {
     { $(
         $(#[$m:meta])*
@@ -117,14 +213,40 @@ errors! {
     Status(tonic::Status),
     MetadataEncoding(tonic::metadata::errors::InvalidMetadataValue),
     Auth(yup_oauth2::Error),
+    Credentials(crate::auth::CredentialsError),
+    Http(hyper::http::Error),
+    Hyper(hyper::Error),
+    Json(serde_json::Error),
     InvalidResponse(String),
+    ParseTable(crate::client::ParseTableError),
+    SessionExpired(crate::client::SessionExpired),
+    Decode(prost::DecodeError),
     Io(std::io::Error),
+    Timeout(tokio::time::error::Elapsed),
+    Append(crate::write::AppendError),
+    Commit(crate::write::CommitError),
+    Finalize(crate::write::FinalizeError),
+    Usage(crate::error::UsageError),
     #[cfg(feature = "arrow")]
     Arrow(arrow::error::ArrowError),
+    #[cfg(feature = "arrow")]
+    Column(crate::row::ColumnError),
+    #[cfg(feature = "avro")]
+    Avro(avro_rs::Error),
+    #[cfg(feature = "serde")]
+    Deserialize(crate::de::DeserializeError),
 }
 
 impl Error {
     pub(crate) fn invalid<S: AsRef<str>>(s: S) -> Self {
         Self::InvalidResponse(s.as_ref().to_string())
     }
+
+    pub(crate) fn usage<S: Into<String>>(message: S) -> Self {
+        Self::Usage(crate::error::UsageError::new(message))
+    }
+
+    pub(crate) fn credentials<S: Into<String>>(message: S) -> Self {
+        Self::Credentials(crate::auth::CredentialsError::new(message))
+    }
 }
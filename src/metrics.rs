@@ -0,0 +1,139 @@
+//! Hooks to export metrics of the calls made to the BigQuery Storage API.
+//!
+//! Implement [`Metrics`](Metrics) on top of your metrics library, e.g. with Prometheus
+//! counters and histograms or OpenTelemetry instruments, and set it with
+//! [`ClientBuilder::metrics`](crate::client::ClientBuilder::metrics) or
+//! [`Client::with_metrics`](crate::client::Client::with_metrics). All methods have a
+//! default implementation that does nothing.
+//! ```rust
+//! use std::sync::atomic::{AtomicU64, Ordering};
+//! use std::time::Duration;
+//!
+//! use bigquery_storage::{Call, Error, Metrics, ResponseMetrics};
+//!
+//! #[derive(Default)]
+//! struct Counters {
+//!     errors: AtomicU64,
+//!     bytes: AtomicU64,
+//!     rows: AtomicU64,
+//!     batches: AtomicU64,
+//! }
+//!
+//! impl Metrics for Counters {
+//!     fn record_call(&self, call: Call, latency: Duration, error: Option<&Error>) {
+//!         println!("{} took {:?}", call, latency);
+//!         if error.is_some() {
+//!             self.errors.fetch_add(1, Ordering::Relaxed);
+//!         }
+//!     }
+//!
+//!     fn record_response(&self, _stream: &str, response: &ResponseMetrics) {
+//!         self.bytes.fetch_add(response.bytes as u64, Ordering::Relaxed);
+//!         self.rows.fetch_add(response.rows as u64, Ordering::Relaxed);
+//!         self.batches.fetch_add(1, Ordering::Relaxed);
+//!     }
+//! }
+//! ```
+//!
+//! With the `tracing` feature, the same calls are also wrapped in
+//! [`tracing`](https://docs.rs/tracing) spans: fetching a token, each call to the API,
+//! the reading of each stream, and the decoding of each of its messages.
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::trace::{Instrument, Span};
+use crate::Error;
+
+/// A call made by a [`Client`](crate::client::Client).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Call {
+    /// Fetching an access token from the [`TokenProvider`](crate::auth::TokenProvider).
+    FetchToken,
+    /// Creating a read session, with its streams.
+    CreateReadSession,
+    /// Opening a `ReadRows` stream, until the server starts responding.
+    ReadRows,
+    /// Splitting a read stream in two.
+    SplitReadStream,
+    /// Creating a stream of a write session.
+    CreateWriteStream,
+    /// Opening an `AppendRows` connection.
+    AppendRows,
+    /// Finalizing a write stream, so that no more rows can be appended to it.
+    FinalizeWriteStream,
+    /// Committing the `PENDING` streams of a write session.
+    BatchCommitWriteStreams,
+    /// Flushing the rows of a `BUFFERED` stream, making them visible to readers.
+    FlushRows,
+}
+
+impl Call {
+    /// The name of the call, e.g. `CreateReadSession`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::FetchToken => "FetchToken",
+            Self::CreateReadSession => "CreateReadSession",
+            Self::ReadRows => "ReadRows",
+            Self::SplitReadStream => "SplitReadStream",
+            Self::CreateWriteStream => "CreateWriteStream",
+            Self::AppendRows => "AppendRows",
+            Self::FinalizeWriteStream => "FinalizeWriteStream",
+            Self::BatchCommitWriteStreams => "BatchCommitWriteStreams",
+            Self::FlushRows => "FlushRows",
+        }
+    }
+}
+
+impl fmt::Display for Call {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The metrics of a `ReadRowsResponse`. Each response carries a single batch of rows.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ResponseMetrics {
+    /// The size of the response, in bytes.
+    pub bytes: usize,
+    /// The number of rows in the response.
+    pub rows: i64,
+    /// The percentage of time the stream was throttled by the server while producing
+    /// the response.
+    pub throttle_percent: i32,
+    /// How long the response was waited for, since the previous response of the
+    /// stream, or since the stream was opened.
+    pub latency: Duration,
+}
+
+/// A hook receiving the metrics of a [`Client`](crate::client::Client), see the
+/// [module documentation](self).
+pub trait Metrics: Send + Sync {
+    /// Record that `call` completed after `latency`, failing with `error` if any.
+    /// Each retry of a call is recorded as a call of its own.
+    fn record_call(&self, _call: Call, _latency: Duration, _error: Option<&Error>) {}
+
+    /// Record a response received while reading `stream`.
+    fn record_response(&self, _stream: &str, _response: &ResponseMetrics) {}
+}
+
+/// Await `f`, the future of `call`, in `span`, and record its latency with `metrics`.
+pub(crate) async fn observe<T, E, F>(
+    metrics: &Option<Arc<dyn Metrics>>,
+    call: Call,
+    span: Span,
+    f: F,
+) -> Result<T, Error>
+where
+    F: Future<Output = Result<T, E>>,
+    E: Into<Error>,
+{
+    let start = Instant::now();
+    let result = f.instrument(span).await.map_err(Into::into);
+    if let Some(metrics) = metrics {
+        metrics.record_call(call, start.elapsed(), result.as_ref().err());
+    }
+    result
+}
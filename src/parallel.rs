@@ -0,0 +1,250 @@
+//! Reading all the streams of a read session concurrently.
+use futures::stream::{self, BoxStream, Stream, StreamExt, TryStreamExt};
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::Error;
+use crate::{ReadSession, RowsStreamReader};
+
+#[cfg(feature = "avro")]
+use crate::AvroRowStream;
+#[cfg(feature = "arrow")]
+use crate::RecordBatchStream;
+
+/// The order in which a [`ParallelReader`](ParallelReader) yields the rows of the
+/// streams it reads concurrently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamOrdering {
+    /// All the rows of a stream are yielded before the rows of the next stream.
+    /// The streams behind the current one are still read ahead, up to the
+    /// [`prefetch`](ParallelReader::prefetch) limit.
+    ByStream,
+    /// Rows are yielded as soon as they are received, from whichever stream.
+    Unordered,
+}
+
+/// A reader merging the streams of a [`ReadSession`](crate::client::ReadSession), reading
+/// several of them concurrently. Create it with [`ReadSession::parallel_reader`](crate::client::ReadSession::parallel_reader).
+///
+/// Streams are taken from the session one at a time, as the reader needs them, so it
+/// shares them fairly with other clones of the session. An error on one stream is
+/// yielded in place and ends that stream only: the others keep being read.
+pub struct ParallelReader {
+    session: ReadSession,
+    concurrency: Option<usize>,
+    ordering: StreamOrdering,
+    prefetch: usize,
+}
+
+impl ParallelReader {
+    pub(crate) fn new(session: ReadSession) -> Self {
+        Self {
+            session,
+            concurrency: None,
+            ordering: StreamOrdering::Unordered,
+            prefetch: 2,
+        }
+    }
+
+    /// Sets the maximum number of streams read at once. If not set, all the streams
+    /// of the session are read at once.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = Some(concurrency.max(1));
+        self
+    }
+
+    /// Sets the order in which rows are yielded. Defaults to [`StreamOrdering::Unordered`](StreamOrdering::Unordered).
+    pub fn ordering(mut self, ordering: StreamOrdering) -> Self {
+        self.ordering = ordering;
+        self
+    }
+
+    /// Sets the number of messages buffered for each stream read ahead, when the
+    /// ordering is [`StreamOrdering::ByStream`](StreamOrdering::ByStream). Defaults to 2.
+    pub fn prefetch(mut self, prefetch: usize) -> Self {
+        self.prefetch = prefetch.max(1);
+        self
+    }
+
+    /// Read the streams into a single [`RecordBatchStream`](crate::read::RecordBatchStream).
+    #[cfg(feature = "arrow")]
+    pub fn into_record_batch_stream(self) -> RecordBatchStream {
+        self.merge(RowsStreamReader::into_record_batch_stream)
+    }
+
+    /// Read the streams into a single [`AvroRowStream`](crate::read::AvroRowStream).
+    #[cfg(feature = "avro")]
+    pub fn into_avro_stream(self) -> AvroRowStream {
+        self.merge(RowsStreamReader::into_avro_stream)
+    }
+
+    fn merge<T, F>(self, decode: F) -> BoxStream<'static, Result<T, Error>>
+    where
+        T: Send + 'static,
+        F: Fn(RowsStreamReader) -> Result<BoxStream<'static, Result<T, Error>>, Error>
+            + Copy
+            + Send
+            + Sync
+            + 'static,
+    {
+        let Self {
+            session,
+            concurrency,
+            ordering,
+            prefetch,
+        } = self;
+
+        let pending = session.clone();
+        let streams = stream::iter(std::iter::from_fn(move || pending.take_stream()))
+            .map(move |name| {
+                let session = session.clone();
+                stream::once(async move {
+                    let rows = match session.open_taken_stream(name).await? {
+                        Some(stream_reader) => decode(stream_reader)?,
+                        None => stream::empty().boxed(),
+                    };
+                    if !session.refreshes() {
+                        return Ok::<_, Error>(rows);
+                    }
+                    // The session may be refreshed, while the stream is opened or read, and
+                    // its work handed over to the streams of the new session, which the
+                    // source may have stopped taking
+                    Ok(rows.chain(drain(session, decode)).boxed())
+                })
+                .try_flatten()
+                .boxed()
+            })
+            .boxed();
+
+        match ordering {
+            StreamOrdering::Unordered => streams.flatten_unordered(concurrency).boxed(),
+            StreamOrdering::ByStream => {
+                let concurrency = concurrency.unwrap_or(usize::MAX);
+                OrderedMerge::new(streams, concurrency, prefetch).boxed()
+            }
+        }
+    }
+}
+
+/// Read the streams left in the queue of `session` one after the other, until it is
+/// empty or a stream fails to open.
+fn drain<T, F>(session: ReadSession, decode: F) -> BoxStream<'static, Result<T, Error>>
+where
+    T: Send + 'static,
+    F: Fn(RowsStreamReader) -> Result<BoxStream<'static, Result<T, Error>>, Error>
+        + Copy
+        + Send
+        + Sync
+        + 'static,
+{
+    stream::unfold(Some(session), move |session| async move {
+        let session = session?;
+        match session.next_stream().await {
+            Ok(Some(stream_reader)) => Some((decode(stream_reader), Some(session))),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+    .try_flatten()
+    .boxed()
+}
+
+/// A stream and the items that were read ahead from it.
+struct Prefetched<T> {
+    stream: BoxStream<'static, T>,
+    buffer: VecDeque<T>,
+    done: bool,
+}
+
+impl<T> Prefetched<T> {
+    fn new(stream: BoxStream<'static, T>) -> Self {
+        Self {
+            stream,
+            buffer: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    fn fill(&mut self, cx: &mut Context<'_>, prefetch: usize) {
+        while !self.done && self.buffer.len() < prefetch {
+            match self.stream.poll_next_unpin(cx) {
+                Poll::Ready(Some(item)) => self.buffer.push_back(item),
+                Poll::Ready(None) => self.done = true,
+                Poll::Pending => break,
+            }
+        }
+    }
+}
+
+/// Flattens a stream of streams, yielding all the items of each stream in turn
+/// while polling up to `concurrency` of them at once.
+struct OrderedMerge<T> {
+    streams: BoxStream<'static, BoxStream<'static, T>>,
+    exhausted: bool,
+    active: VecDeque<Prefetched<T>>,
+    concurrency: usize,
+    prefetch: usize,
+}
+
+// No field is ever pinned: the streams are all boxed.
+impl<T> Unpin for OrderedMerge<T> {}
+
+impl<T> OrderedMerge<T> {
+    fn new(
+        streams: BoxStream<'static, BoxStream<'static, T>>,
+        concurrency: usize,
+        prefetch: usize,
+    ) -> Self {
+        Self {
+            streams,
+            exhausted: false,
+            active: VecDeque::new(),
+            concurrency,
+            prefetch,
+        }
+    }
+}
+
+impl<T> Stream for OrderedMerge<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let this = self.get_mut();
+        loop {
+            while !this.exhausted && this.active.len() < this.concurrency {
+                match this.streams.poll_next_unpin(cx) {
+                    Poll::Ready(Some(stream)) => this.active.push_back(Prefetched::new(stream)),
+                    Poll::Ready(None) => this.exhausted = true,
+                    Poll::Pending => break,
+                }
+            }
+
+            let prefetch = this.prefetch;
+            for ahead in this.active.iter_mut().skip(1) {
+                ahead.fill(cx, prefetch);
+            }
+
+            let head = match this.active.front_mut() {
+                Some(head) => head,
+                None if this.exhausted => return Poll::Ready(None),
+                None => return Poll::Pending,
+            };
+
+            if let Some(item) = head.buffer.pop_front() {
+                return Poll::Ready(Some(item));
+            }
+
+            if !head.done {
+                match head.stream.poll_next_unpin(cx) {
+                    Poll::Ready(Some(item)) => return Poll::Ready(Some(item)),
+                    Poll::Ready(None) => {}
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+
+            this.active.pop_front();
+        }
+    }
+}
@@ -1,17 +1,39 @@
-use tonic::Streaming;
+use prost::Message;
+use tonic::{Status, Streaming};
 
-use futures::future::ready;
-use futures::stream::{StreamExt, TryStreamExt};
+use futures::future::{ready, BoxFuture, FutureExt};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
 
-use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use crate::googleapis::{
-    read_rows_response::Rows, read_session::Schema, ArrowRecordBatch, ArrowSchema, ReadRowsResponse,
-};
+use crate::error::is_session_expired;
+use crate::googleapis::{read_rows_response::Rows, read_session::Schema, ReadRowsResponse};
+use crate::handle::ReadStreamHandle;
+use crate::metrics::{Metrics, ResponseMetrics};
+use crate::timeout::CallLimits;
+use crate::trace::{span, Instrument, Span};
 use crate::Error;
+use crate::RetryPolicy;
 
+#[cfg(feature = "arrow")]
+use crate::googleapis::{ArrowRecordBatch, ArrowSchema};
 #[cfg(feature = "arrow")]
 use arrow::ipc::reader::StreamReader as ArrowStreamReader;
+#[cfg(feature = "arrow")]
+use arrow::record_batch::RecordBatch;
+#[cfg(feature = "arrow")]
+use std::io::Cursor;
+
+#[cfg(feature = "avro")]
+use crate::googleapis::{AvroRows, AvroSchema};
+#[cfg(feature = "avro")]
+use avro_rs::types::Value as AvroValue;
+
+#[cfg(feature = "serde")]
+use crate::de::{self, DeserializeStream};
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
 
 /// Remove the continuation bytes segment of a valid Arrow IPC message
 #[cfg(feature = "arrow")]
@@ -27,48 +49,634 @@ fn strip_continuation_bytes(msg: &[u8]) -> Result<&[u8], Error> {
     }
 }
 
+/// Extract the serialized Arrow schema of a read session
+#[cfg(feature = "arrow")]
+fn arrow_schema(schema: Schema) -> Result<Vec<u8>, Error> {
+    match schema {
+        Schema::ArrowSchema(ArrowSchema { serialized_schema }) => Ok(serialized_schema),
+        _ => Err(Error::invalid("expected arrow schema")),
+    }
+}
+
+/// Extract the serialized Arrow record batch of a `ReadRowsResponse`
+#[cfg(feature = "arrow")]
+fn arrow_record_batch(resp: ReadRowsResponse) -> Result<Vec<u8>, Error> {
+    let ReadRowsResponse { rows, .. } = resp;
+    rows.ok_or(Error::invalid("no rows received"))
+        .and_then(|rows| match rows {
+            Rows::ArrowRecordBatch(ArrowRecordBatch {
+                serialized_record_batch,
+                ..
+            }) => Ok(serialized_record_batch),
+            _ => {
+                let err = Error::invalid("expected arrow record batch");
+                Err(err)
+            }
+        })
+}
+
+/// Decode a single Arrow IPC record batch message against the schema of
+/// its stream. `schema` is expected to be stripped of its continuation bytes.
+#[cfg(feature = "arrow")]
+fn decode_record_batch(schema: &[u8], msg: &[u8]) -> Result<RecordBatch, Error> {
+    let body = strip_continuation_bytes(msg)?;
+
+    let mut buf = Vec::with_capacity(schema.len() + body.len() + 4);
+    buf.extend(schema);
+    buf.extend(body);
+    buf.extend(&[0u8; 4]);
+
+    let mut reader = ArrowStreamReader::try_new(Cursor::new(buf))?;
+    let record_batch = reader
+        .next()
+        .ok_or(Error::invalid("empty arrow record batch"))??;
+
+    Ok(record_batch)
+}
+
+/// Parse the Avro schema of a read session
+#[cfg(feature = "avro")]
+fn avro_schema(schema: Schema) -> Result<avro_rs::Schema, Error> {
+    match schema {
+        Schema::AvroSchema(AvroSchema { schema }) => Ok(avro_rs::Schema::parse_str(&schema)?),
+        _ => Err(Error::invalid("expected avro schema")),
+    }
+}
+
+/// Extract the serialized Avro rows of a `ReadRowsResponse`
+#[cfg(feature = "avro")]
+fn avro_rows(resp: ReadRowsResponse) -> Result<Vec<u8>, Error> {
+    let ReadRowsResponse { rows, .. } = resp;
+    rows.ok_or(Error::invalid("no rows received"))
+        .and_then(|rows| match rows {
+            Rows::AvroRows(AvroRows {
+                serialized_binary_rows,
+                ..
+            }) => Ok(serialized_binary_rows),
+            _ => {
+                let err = Error::invalid("expected avro rows");
+                Err(err)
+            }
+        })
+}
+
+/// Decode a block of concatenated binary Avro datums
+#[cfg(feature = "avro")]
+fn decode_avro_rows(schema: &avro_rs::Schema, mut rows: &[u8]) -> Result<Vec<AvroValue>, Error> {
+    let mut values = Vec::new();
+    while !rows.is_empty() {
+        let value = avro_rs::from_avro_datum(schema, &mut rows, None)?;
+        values.push(value);
+    }
+    Ok(values)
+}
+
 #[cfg(feature = "arrow")]
 pub type DefaultArrowStreamReader = ArrowStreamReader<Cursor<Vec<u8>>>;
 
+/// A [`Stream`](futures::stream::Stream) of Arrow [RecordBatch](arrow::record_batch::RecordBatch)es,
+/// as returned by [`RowsStreamReader::into_record_batch_stream`](RowsStreamReader::into_record_batch_stream).
+#[cfg(feature = "arrow")]
+pub type RecordBatchStream = BoxStream<'static, Result<RecordBatch, Error>>;
+
+/// A [`Stream`](futures::stream::Stream) of Avro [values](avro_rs::types::Value), one per row,
+/// as returned by [`RowsStreamReader::into_avro_stream`](RowsStreamReader::into_avro_stream).
+#[cfg(feature = "avro")]
+pub type AvroRowStream = BoxStream<'static, Result<AvroValue, Error>>;
+
+/// The progress of a [`RowsStreamReader`](RowsStreamReader), as reported by the server.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StreamProgress {
+    /// The fraction of the rows of the stream that have been read, between 0 and 1,
+    /// as of the last response received. This is an approximation: the server only
+    /// reports it when it is known, and it is 0 until then.
+    pub fraction_consumed: f64,
+    /// The number of rows received so far.
+    pub rows_read: i64,
+    /// The percentage of time the stream was throttled by the server while
+    /// producing the last response received. 0 means no throttling.
+    pub throttle_percent: i32,
+}
+
+/// A handle to the [`StreamProgress`](StreamProgress) of a [`RowsStreamReader`](RowsStreamReader),
+/// which remains valid while the reader is being consumed. Get it with
+/// [`RowsStreamReader::progress`](RowsStreamReader::progress).
+#[derive(Clone, Debug, Default)]
+pub struct ProgressHandle {
+    inner: Arc<Mutex<StreamProgress>>,
+}
+
+impl ProgressHandle {
+    /// The current progress of the stream.
+    pub fn get(&self) -> StreamProgress {
+        *self.inner.lock().unwrap()
+    }
+
+    fn update(&self, resp: &ReadRowsResponse) -> StreamProgress {
+        let mut progress = self.inner.lock().unwrap();
+        progress.rows_read += resp.row_count;
+        if let Some(at_response_end) = resp
+            .stats
+            .as_ref()
+            .and_then(|stats| stats.progress.as_ref())
+            .map(|progress| progress.at_response_end)
+        {
+            progress.fraction_consumed = at_response_end;
+        }
+        progress.throttle_percent = resp
+            .throttle_state
+            .as_ref()
+            .map(|throttle_state| throttle_state.throttle_percent)
+            .unwrap_or_default();
+        *progress
+    }
+}
+
+/// A callback invoked with the progress of a stream after each response.
+pub(crate) type ProgressCallback = Box<dyn Fn(&StreamProgress) + Send>;
+
+/// Re-issues `ReadRows` on a given stream, starting at a given row offset.
+pub(crate) type Reconnect = Box<
+    dyn Fn(&str, i64) -> BoxFuture<'static, Result<Streaming<ReadRowsResponse>, Error>>
+        + Send
+        + Sync,
+>;
+
+/// Splits a given stream at a given fraction of its rows, into the name of the primary
+/// stream and a handle to the remainder stream. Returns `None` if it cannot be split.
+pub(crate) type Split = Box<
+    dyn Fn(String, f64) -> BoxFuture<'static, Result<Option<(String, ReadStreamHandle)>, Error>>
+        + Send
+        + Sync,
+>;
+
+/// Whether rows of a read session were delivered, after which its work can no longer
+/// be handed over to a refreshed session, or whether it was handed over. Shared by the
+/// session and the readers of its streams.
+#[derive(Debug, Default)]
+pub(crate) struct SessionClaim {
+    pub(crate) delivered: bool,
+    pub(crate) retired: bool,
+}
+
+/// Hands the work of a stream over to a refreshed session when its session expired.
+pub(crate) struct Handover {
+    pub(crate) claim: Arc<Mutex<SessionClaim>>,
+    /// Refreshes the session, unless another reader already did. Fails with
+    /// [`Error::SessionExpired`](crate::Error::SessionExpired) if rows of the session
+    /// were delivered.
+    pub(crate) refresh: Box<dyn Fn() -> BoxFuture<'static, Result<(), Error>> + Send + Sync>,
+}
+
+/// Opens a given stream of the session of a reader, such as the remainder of a split.
+pub(crate) type Open =
+    Box<dyn Fn(String) -> BoxFuture<'static, Result<RowsStreamReader, Error>> + Send + Sync>;
+
+/// The stream read by a [`RowsStreamReader`](RowsStreamReader), shared with its
+/// [`StreamSplitter`](StreamSplitter)s.
+#[derive(Debug)]
+struct SplitState {
+    /// The stream to split next: the primary stream of the last split, if any.
+    stream: String,
+    /// The primary stream the reader has to carry on with, before reading its next
+    /// response.
+    primary: Option<String>,
+}
+
+/// A handle to split the stream of a [`RowsStreamReader`](RowsStreamReader), which
+/// remains valid while the reader is being consumed. Get it with
+/// [`RowsStreamReader::splitter`](RowsStreamReader::splitter).
+#[derive(Clone)]
+pub struct StreamSplitter {
+    state: Arc<tokio::sync::Mutex<SplitState>>,
+    split: Option<Arc<Split>>,
+    progress: ProgressHandle,
+}
+
+impl StreamSplitter {
+    /// Split the stream at approximately `fraction` of its rows (strictly between 0
+    /// and 1), into a primary stream, which the reader carries on with, and a remainder
+    /// stream, returned to be read elsewhere, e.g. with [`Client::read_stream`](crate::client::Client::read_stream).
+    /// This is useful to hand part of the work of a straggler to another executor.
+    /// Returns `None` if the stream cannot be split, e.g. if it is too small.
+    ///
+    /// The primary stream starts with the same rows as the original stream, so the
+    /// reader resumes it at the row it reached and no row is read twice, provided the
+    /// split point is past that row. A `fraction` up to the [`fraction_consumed`](StreamProgress::fraction_consumed)
+    /// of the stream is rejected with [`Error::Usage`](crate::Error::Usage): past the
+    /// split point, the rows the reader received would be read again from the remainder.
+    /// As the reader keeps going during the split, leave it some room.
+    ///
+    /// To get readers of both streams instead, use [`RowsStreamReader::split`](RowsStreamReader::split).
+    pub async fn split(&self, fraction: f64) -> Result<Option<ReadStreamHandle>, Error> {
+        let split = match &self.split {
+            Some(split) => split,
+            None => return Ok(None),
+        };
+        let mut state = self.state.lock().await;
+        let consumed = self.progress.get().fraction_consumed;
+        if fraction <= consumed {
+            return Err(Error::usage(format!(
+                "cannot split a stream at {}, {} of it was already read",
+                fraction, consumed
+            )));
+        }
+        match split(state.stream.clone(), fraction).await? {
+            Some((primary, remainder)) => {
+                state.stream = primary.clone();
+                state.primary = Some(primary);
+                Ok(Some(remainder))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// The next message of `upstream`, failing if none is received for `idle_timeout`.
+async fn next_message(
+    upstream: &mut Streaming<ReadRowsResponse>,
+    idle_timeout: Option<Duration>,
+) -> Result<Result<Option<ReadRowsResponse>, Status>, Error> {
+    match idle_timeout {
+        Some(idle_timeout) => Ok(tokio::time::timeout(idle_timeout, upstream.message()).await?),
+        None => Ok(upstream.message().await),
+    }
+}
+
+/// The rows of a stream, as they are received from `ReadRows`. Keeps track of
+/// the offset of the next row so the call can be resumed after a transient failure.
+struct ResumableRows {
+    stream: String,
+    upstream: Streaming<ReadRowsResponse>,
+    offset: i64,
+    reconnect: Reconnect,
+    splits: Arc<tokio::sync::Mutex<SplitState>>,
+    handover: Option<Handover>,
+    retry_policy: RetryPolicy,
+    limits: CallLimits,
+    /// When reading the whole stream must be done by, retries included.
+    deadline: Option<Instant>,
+    progress: ProgressHandle,
+    on_progress: Option<ProgressCallback>,
+    metrics: Option<Arc<dyn Metrics>>,
+    /// When the last response was received, or the stream opened.
+    last_response: Instant,
+    span: Span,
+}
+
+impl ResumableRows {
+    async fn next(&mut self) -> Result<Option<ReadRowsResponse>, Error> {
+        let limits = self.limits.clone();
+        let span = self.span.clone();
+        let next = limits.run(self.deadline, self.next_unbounded());
+        next.instrument(span).await?
+    }
+
+    async fn next_unbounded(&mut self) -> Result<Option<ReadRowsResponse>, Error> {
+        let resp = match self.message().await? {
+            Ok(resp) => resp,
+            Err(status) => self.resume(status).await?,
+        };
+        let resp = match resp {
+            Some(resp) => resp,
+            None => return Ok(None),
+        };
+        if resp.row_count > 0 && !self.claim() {
+            return Ok(None);
+        }
+
+        self.offset += resp.row_count;
+        let progress = self.progress.update(&resp);
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(&progress);
+        }
+        if let Some(metrics) = &self.metrics {
+            let response = ResponseMetrics {
+                bytes: resp.encoded_len(),
+                rows: resp.row_count,
+                throttle_percent: progress.throttle_percent,
+                latency: self.last_response.elapsed(),
+            };
+            metrics.record_response(&self.stream, &response);
+        }
+        self.last_response = Instant::now();
+        Ok(Some(resp))
+    }
+
+    /// Re-issue the call after it failed with `status`, from the offset reached, and read
+    /// its next message, as allowed by the retry policy. The failed read counts as the
+    /// first attempt. Returns `None` if the stream ended, or was handed over.
+    async fn resume(&mut self, status: Status) -> Result<Option<ReadRowsResponse>, Error> {
+        let reconnect = &self.reconnect;
+        let stream = self.stream.as_str();
+        let offset = self.offset;
+        let idle_timeout = self.limits.idle_timeout;
+        let mut failure = Some(status);
+        let resumed = self.retry_policy.retry(|| match failure.take() {
+            Some(status) => ready(Err(status.into())).boxed(),
+            None => {
+                let connect = reconnect(stream, offset);
+                async move {
+                    let mut upstream = connect.await?;
+                    let resp = next_message(&mut upstream, idle_timeout).await??;
+                    Ok((upstream, resp))
+                }
+                .boxed()
+            }
+        });
+
+        match resumed.await {
+            Ok((upstream, resp)) => {
+                self.upstream = upstream;
+                Ok(resp)
+            }
+            Err(Error::Status(status)) if is_session_expired(&status) => {
+                // Hand the work of the stream over to a refreshed session, whose
+                // streams the rows are then read from
+                let refresh = match &self.handover {
+                    Some(handover) => (handover.refresh)(),
+                    None => return Err(status.into()),
+                };
+                refresh.await?;
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Claim the rows of the session before delivering the first rows of the stream.
+    /// Returns false if the session was refreshed, its work handed over to the new one.
+    fn claim(&self) -> bool {
+        match &self.handover {
+            Some(handover) if self.offset == 0 => {
+                let mut claim = handover.claim.lock().unwrap();
+                if claim.retired {
+                    return false;
+                }
+                claim.delivered = true;
+                true
+            }
+            _ => true,
+        }
+    }
+
+    /// The next message of the stream, once it switched to the primary stream of the
+    /// last split, if any. A failure to switch is reported like a failure to read.
+    async fn message(&mut self) -> Result<Result<Option<ReadRowsResponse>, Status>, Error> {
+        // While a split is in progress, keep reading the original stream
+        let primary = match self.splits.try_lock() {
+            Ok(mut state) => state.primary.take(),
+            Err(_) => None,
+        };
+        if let Some(primary) = primary {
+            self.stream = primary;
+            match (self.reconnect)(&self.stream, self.offset).await {
+                Ok(upstream) => self.upstream = upstream,
+                Err(Error::Status(status)) => return Ok(Err(status)),
+                Err(err) => return Err(err),
+            }
+        }
+        next_message(&mut self.upstream, self.limits.idle_timeout).await
+    }
+
+    fn into_stream(self) -> BoxStream<'static, Result<ReadRowsResponse, Error>> {
+        stream::unfold(Some(self), |rows| async move {
+            let mut rows = match rows {
+                Some(rows) => rows,
+                None => return None,
+            };
+            match rows.next().await {
+                Ok(Some(resp)) => Some((Ok(resp), Some(rows))),
+                Ok(None) => None,
+                // The stream cannot be resumed past a fatal error
+                Err(err) => Some((Err(err), None)),
+            }
+        })
+        .boxed()
+    }
+}
+
 /// A wrapper around a [BigQuery Storage stream](https://cloud.google.com/bigquery/docs/reference/storage#read_from_a_session_stream).
+///
+/// Transient failures while reading are retried according to the
+/// [`RetryPolicy`](crate::retry::RetryPolicy) of the [`Client`](crate::client::Client),
+/// resuming from the last row received.
 pub struct RowsStreamReader {
+    name: String,
     schema: Schema,
-    upstream: Streaming<ReadRowsResponse>,
+    rows: ResumableRows,
+    split: Option<Arc<Split>>,
+    open: Option<Open>,
 }
 
 impl RowsStreamReader {
-    pub(crate) fn new(schema: Schema, upstream: Streaming<ReadRowsResponse>) -> Self {
-        Self { schema, upstream }
+    pub(crate) fn new(
+        name: String,
+        schema: Schema,
+        upstream: Streaming<ReadRowsResponse>,
+        reconnect: Reconnect,
+        retry_policy: RetryPolicy,
+        limits: CallLimits,
+        deadline: Option<Instant>,
+    ) -> Self {
+        let split = SplitState {
+            stream: name.clone(),
+            primary: None,
+        };
+        let rows = ResumableRows {
+            stream: name.clone(),
+            upstream,
+            offset: 0,
+            reconnect,
+            splits: Arc::new(tokio::sync::Mutex::new(split)),
+            handover: None,
+            retry_policy,
+            limits,
+            deadline,
+            progress: ProgressHandle::default(),
+            on_progress: None,
+            metrics: None,
+            last_response: Instant::now(),
+            span: span!(DEBUG, "read_stream", stream = %name),
+        };
+        Self {
+            name,
+            schema,
+            rows,
+            split: None,
+            open: None,
+        }
+    }
+
+    /// Record the metrics of the responses of this stream with `metrics`.
+    pub(crate) fn with_metrics(mut self, metrics: Option<Arc<dyn Metrics>>) -> Self {
+        self.rows.metrics = metrics;
+        self
+    }
+
+    /// Hand the work of this stream over with `handover` when its session expired.
+    pub(crate) fn with_handover(mut self, handover: Handover) -> Self {
+        self.rows.handover = Some(handover);
+        self
+    }
+
+    /// Split this stream with `split` when asked to by a [`StreamSplitter`](StreamSplitter),
+    /// and read the remainder with `open`.
+    pub(crate) fn with_split(mut self, split: Split, open: Open) -> Self {
+        self.split = Some(Arc::new(split));
+        self.open = Some(open);
+        self
+    }
+
+    /// The name of the underlying read stream, of the form
+    /// `projects/{project_id}/locations/{location}/sessions/{session_id}/streams/{stream_id}`.
+    pub fn stream_name(&self) -> &str {
+        &self.name
+    }
+
+    /// A handle to the progress of this stream, which can be polled while the
+    /// stream is being consumed, e.g. to render a progress bar.
+    pub fn progress(&self) -> ProgressHandle {
+        self.rows.progress.clone()
+    }
+
+    /// A handle to split this stream, which can be used while the stream is being
+    /// consumed, e.g. when it lags behind the other streams of its session.
+    pub fn splitter(&self) -> StreamSplitter {
+        StreamSplitter {
+            state: self.rows.splits.clone(),
+            split: self.split.clone(),
+            progress: self.rows.progress.clone(),
+        }
+    }
+
+    /// Split this stream at approximately `fraction` of its rows, into a reader of the
+    /// primary stream, resumed at the row this reader reached, and a reader of the
+    /// remainder stream, e.g. to read it concurrently. Returns this reader unchanged,
+    /// and no remainder, if the stream cannot be split, e.g. if it is too small.
+    ///
+    /// `fraction` must be greater than the [`fraction_consumed`](StreamProgress::fraction_consumed)
+    /// of the stream, see [`StreamSplitter::split`](StreamSplitter::split).
+    pub async fn split(mut self, fraction: f64) -> Result<(Self, Option<Self>), Error> {
+        let remainder = match self.splitter().split(fraction).await? {
+            Some(remainder) => remainder,
+            None => return Ok((self, None)),
+        };
+        let open = self
+            .open
+            .as_ref()
+            .ok_or_else(|| Error::usage("cannot open the remainder of the split"))?;
+        let remainder = open(remainder.stream().to_string()).await?;
+        self.name = self.rows.splits.lock().await.stream.clone();
+        Ok((self, Some(remainder)))
+    }
+
+    /// Set a callback, invoked with the progress of this stream each time a response
+    /// is received. Useful to detect throttling as soon as it happens.
+    pub fn on_progress<F>(mut self, on_progress: F) -> Self
+    where
+        F: Fn(&StreamProgress) + Send + 'static,
+    {
+        self.rows.on_progress = Some(Box::new(on_progress));
+        self
+    }
+
+    /// Consume the stream into a [`RecordBatchStream`](RecordBatchStream). Each
+    /// message is decoded into a [RecordBatch](arrow::record_batch::RecordBatch) as soon as it
+    /// is received, so only one message is held in memory at a time.
+    #[cfg(feature = "arrow")]
+    pub fn into_record_batch_stream(self) -> Result<RecordBatchStream, Error> {
+        let serialized_schema = arrow_schema(self.schema)?;
+        let schema = strip_continuation_bytes(serialized_schema.as_slice())?.to_vec();
+
+        let record_batch_stream = self
+            .rows
+            .into_stream()
+            .and_then(|resp| ready(arrow_record_batch(resp)))
+            .and_then(move |msg| {
+                let span = span!(TRACE, "decode_record_batch", bytes = msg.len());
+                ready(span.in_scope(|| decode_record_batch(schema.as_slice(), msg.as_slice())))
+            })
+            .boxed();
+
+        Ok(record_batch_stream)
+    }
+
+    /// Consume the stream into an [`AvroRowStream`](AvroRowStream). Each message is
+    /// decoded against the session's Avro schema as soon as it is received. The
+    /// session must have been created with [`DataFormat::Avro`](crate::googleapis::DataFormat::Avro).
+    #[cfg(feature = "avro")]
+    pub fn into_avro_stream(self) -> Result<AvroRowStream, Error> {
+        let schema = avro_schema(self.schema)?;
+
+        let avro_row_stream = self
+            .rows
+            .into_stream()
+            .and_then(|resp| ready(avro_rows(resp)))
+            .and_then(move |rows| {
+                let span = span!(TRACE, "decode_avro_rows", bytes = rows.len());
+                ready(span.in_scope(|| decode_avro_rows(&schema, rows.as_slice())))
+            })
+            .map_ok(|values| stream::iter(values.into_iter().map(Ok)))
+            .try_flatten()
+            .boxed();
+
+        Ok(avro_row_stream)
+    }
+
+    /// Consume the stream into a [`DeserializeStream`](crate::de::DeserializeStream) of
+    /// rows deserialized into `T`, from either an Arrow or an Avro session. Columns are
+    /// matched to fields by name, nested records to nested structs and repeated fields
+    /// to sequences. Errors name the column that could not be deserialized.
+    #[cfg(feature = "serde")]
+    pub fn into_deserialize<T>(self) -> Result<DeserializeStream<T>, Error>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        match self.schema {
+            #[cfg(feature = "arrow")]
+            Schema::ArrowSchema(_) => {
+                let rows = self
+                    .into_record_batch_stream()?
+                    .map(|batch| {
+                        let rows = match batch {
+                            Ok(batch) => de::from_record_batch(&batch),
+                            Err(err) => vec![Err(err)],
+                        };
+                        stream::iter(rows)
+                    })
+                    .flatten()
+                    .boxed();
+                Ok(rows)
+            }
+            #[cfg(feature = "avro")]
+            Schema::AvroSchema(_) => {
+                let rows = self
+                    .into_avro_stream()?
+                    .map(|value| value.and_then(de::from_avro_value))
+                    .boxed();
+                Ok(rows)
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(Error::invalid(
+                "the data format of the session is not enabled by the features of this crate",
+            )),
+        }
     }
 
     /// Consume the entire stream into an Arrow [StreamReader](arrow::ipc::reader::StreamReader).
+    ///
+    /// This buffers the whole stream in memory before returning; prefer
+    /// [`into_record_batch_stream`](RowsStreamReader::into_record_batch_stream) for large streams.
     #[cfg(feature = "arrow")]
     pub async fn into_arrow_reader(self) -> Result<DefaultArrowStreamReader, Error> {
         let mut serialized_arrow_stream = self
-            .upstream
-            .map_err(|e| e.into())
-            .and_then(|resp| {
-                let ReadRowsResponse { rows, .. } = resp;
-                let out =
-                    rows.ok_or(Error::invalid("no rows received"))
-                        .and_then(|rows| match rows {
-                            Rows::ArrowRecordBatch(ArrowRecordBatch {
-                                serialized_record_batch,
-                                ..
-                            }) => Ok(serialized_record_batch),
-                            _ => {
-                                let err = Error::invalid("expected arrow record batch");
-                                Err(err)
-                            }
-                        });
-                ready(out)
-            })
+            .rows
+            .into_stream()
+            .and_then(|resp| ready(arrow_record_batch(resp)))
             .boxed();
 
-        let serialized_schema = match self.schema {
-            Schema::ArrowSchema(ArrowSchema { serialized_schema }) => serialized_schema,
-            _ => return Err(Error::invalid("expected arrow schema")),
-        };
+        let serialized_schema = arrow_schema(self.schema)?;
 
         let mut buf = Vec::new();
         buf.extend(strip_continuation_bytes(serialized_schema.as_slice())?);
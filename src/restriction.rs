@@ -0,0 +1,464 @@
+//! Building row restrictions without formatting SQL by hand.
+//!
+//! A [`ReadSessionBuilder::row_restriction`](crate::client::ReadSessionBuilder::row_restriction)
+//! is a SQL filter, similar to a `WHERE` clause. The expressions of this module render to
+//! such filters, quoting column names and escaping literals, and are passed to
+//! [`ReadSessionBuilder::filter`](crate::client::ReadSessionBuilder::filter).
+//! ```rust
+//! use bigquery_storage::{col, Literal};
+//!
+//! let start = Literal::date(2014, 9, 1).unwrap();
+//! let end = Literal::date(2014, 9, 30).unwrap();
+//! let filter = col("int_field")
+//!     .gt(5)
+//!     .and(col("date_field").between(start, end))
+//!     .and(col("name").ne("O'Brien"));
+//!
+//! assert_eq!(
+//!     filter.to_string(),
+//!     "((`int_field` > 5) AND (`date_field` BETWEEN DATE '2014-09-01' AND DATE '2014-09-30')) \
+//!      AND (`name` != 'O\\'Brien')"
+//! );
+//! ```
+use std::fmt::Write;
+use std::time::SystemTime;
+
+use crate::handle::system_time_to_timestamp;
+
+/// A column of the table, see [`col`](col).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    path: Vec<String>,
+}
+
+/// The column `name`. Fields of `STRUCT` columns are accessed by their path, e.g.
+/// `address.city`.
+pub fn col(name: &str) -> Column {
+    Column {
+        path: name.split('.').map(str::to_string).collect(),
+    }
+}
+
+/// A literal value in a row restriction.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    Null,
+    Bool(bool),
+    Int64(i64),
+    Float64(f64),
+    /// A `NUMERIC`, in its decimal representation.
+    Numeric(String),
+    /// A `BIGNUMERIC`, in its decimal representation.
+    BigNumeric(String),
+    String(String),
+    Bytes(Vec<u8>),
+    Date(Date),
+    Timestamp(SystemTime),
+}
+
+impl Literal {
+    /// A `DATE` literal, or `None` if the date does not exist or is out of the range of
+    /// BigQuery, years 1 to 9999.
+    pub fn date(year: i32, month: u32, day: u32) -> Option<Self> {
+        Date::new(year, month, day).map(Self::Date)
+    }
+
+    /// A `NUMERIC` literal, from its decimal representation such as `"1.25"`.
+    pub fn numeric<S: Into<String>>(value: S) -> Self {
+        Self::Numeric(value.into())
+    }
+
+    /// A `BIGNUMERIC` literal, from its decimal representation.
+    pub fn bignumeric<S: Into<String>>(value: S) -> Self {
+        Self::BigNumeric(value.into())
+    }
+}
+
+/// A valid `DATE`, in the proleptic Gregorian calendar. Build it with
+/// [`Literal::date`](Literal::date).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Date {
+    year: i32,
+    month: u32,
+    day: u32,
+}
+
+impl Date {
+    fn new(year: i32, month: u32, day: u32) -> Option<Self> {
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days_in_month = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if leap => 29,
+            2 => 28,
+            _ => return None,
+        };
+        if !(1..=9999).contains(&year) || !(1..=days_in_month).contains(&day) {
+            return None;
+        }
+        Some(Self { year, month, day })
+    }
+
+    /// The year, from 1 to 9999.
+    pub fn year(&self) -> i32 {
+        self.year
+    }
+
+    /// The month, from 1 to 12.
+    pub fn month(&self) -> u32 {
+        self.month
+    }
+
+    /// The day of the month, from 1.
+    pub fn day(&self) -> u32 {
+        self.day
+    }
+}
+
+macro_rules! literal_from {
+    { $($ty:ty => $variant:ident,)* } => {
+        $(
+            impl From<$ty> for Literal {
+                fn from(value: $ty) -> Self {
+                    Self::$variant(value.into())
+                }
+            }
+        )*
+    };
+}
+
+literal_from! {
+    bool => Bool,
+    i8 => Int64,
+    i16 => Int64,
+    i32 => Int64,
+    i64 => Int64,
+    u8 => Int64,
+    u16 => Int64,
+    u32 => Int64,
+    f32 => Float64,
+    f64 => Float64,
+    &str => String,
+    String => String,
+    &[u8] => Bytes,
+    Vec<u8> => Bytes,
+    SystemTime => Timestamp,
+}
+
+impl<T: Into<Literal>> From<Option<T>> for Literal {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Self::Null)
+    }
+}
+
+impl std::fmt::Display for Column {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, name) in self.path.iter().enumerate() {
+            if i > 0 {
+                f.write_str(".")?;
+            }
+            write_quoted(f, name, '`')?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Null => f.write_str("NULL"),
+            Self::Bool(true) => f.write_str("TRUE"),
+            Self::Bool(false) => f.write_str("FALSE"),
+            Self::Int64(value) => write!(f, "{}", value),
+            Self::Float64(value) if value.is_nan() => f.write_str("CAST('nan' AS FLOAT64)"),
+            Self::Float64(value) if value.is_infinite() && *value > 0.0 => {
+                f.write_str("CAST('inf' AS FLOAT64)")
+            }
+            Self::Float64(value) if value.is_infinite() => f.write_str("CAST('-inf' AS FLOAT64)"),
+            Self::Float64(value) => write!(f, "{:?}", value),
+            Self::Numeric(value) => {
+                f.write_str("NUMERIC ")?;
+                write_quoted(f, value, '\'')
+            }
+            Self::BigNumeric(value) => {
+                f.write_str("BIGNUMERIC ")?;
+                write_quoted(f, value, '\'')
+            }
+            Self::String(value) => write_quoted(f, value, '\''),
+            Self::Bytes(value) => {
+                f.write_str("b")?;
+                write_quoted_bytes(f, value)
+            }
+            Self::Date(date) => write!(
+                f,
+                "DATE '{:04}-{:02}-{:02}'",
+                date.year, date.month, date.day
+            ),
+            Self::Timestamp(time) => {
+                let timestamp = system_time_to_timestamp(*time);
+                let days = timestamp.seconds.div_euclid(86_400);
+                let seconds = timestamp.seconds.rem_euclid(86_400);
+                let (year, month, day) = civil_from_days(days);
+                write!(
+                    f,
+                    "TIMESTAMP '{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:06}+00'",
+                    year,
+                    month,
+                    day,
+                    seconds / 3600,
+                    seconds / 60 % 60,
+                    seconds % 60,
+                    timestamp.nanos / 1000
+                )
+            }
+        }
+    }
+}
+
+/// Write `value` between `quote`s, escaped as in BigQuery string literals and quoted
+/// identifiers.
+fn write_quoted(f: &mut std::fmt::Formatter<'_>, value: &str, quote: char) -> std::fmt::Result {
+    f.write_char(quote)?;
+    for c in value.chars() {
+        match c {
+            c if c.is_ascii() => write_escaped(f, c as u8, quote)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char(quote)
+}
+
+/// Write `value` between single quotes as a bytes literal, without the `b` prefix. Bytes
+/// that are not printable ASCII are written as `\xhh`.
+fn write_quoted_bytes(f: &mut std::fmt::Formatter<'_>, value: &[u8]) -> std::fmt::Result {
+    f.write_char('\'')?;
+    for byte in value {
+        match byte {
+            byte if byte.is_ascii() => write_escaped(f, *byte, '\'')?,
+            byte => write!(f, "\\x{:02x}", byte)?,
+        }
+    }
+    f.write_char('\'')
+}
+
+fn write_escaped(f: &mut std::fmt::Formatter<'_>, byte: u8, quote: char) -> std::fmt::Result {
+    match byte {
+        b'\\' => f.write_str("\\\\"),
+        b'\n' => f.write_str("\\n"),
+        b'\r' => f.write_str("\\r"),
+        b'\t' => f.write_str("\\t"),
+        byte if byte as char == quote => write!(f, "\\{}", quote),
+        byte if byte.is_ascii_control() => write!(f, "\\x{:02x}", byte),
+        byte => f.write_char(byte as char),
+    }
+}
+
+/// The year, month and day of the `days`th day since the Unix epoch, in the proleptic
+/// Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// A boolean expression on the columns of a row, rendered to SQL with `to_string`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expr {
+    sql: String,
+}
+
+impl Expr {
+    /// An expression from raw SQL, such as `st_equals(geo_field, st_geofromtext('POINT(2 2)'))`.
+    /// It is not escaped in any way.
+    pub fn raw<S: Into<String>>(sql: S) -> Self {
+        Self { sql: sql.into() }
+    }
+
+    /// Both `self` and `other`.
+    pub fn and(self, other: Expr) -> Self {
+        Self::raw(format!("({}) AND ({})", self.sql, other.sql))
+    }
+
+    /// Either `self` or `other`.
+    pub fn or(self, other: Expr) -> Self {
+        Self::raw(format!("({}) OR ({})", self.sql, other.sql))
+    }
+}
+
+impl std::ops::Not for Expr {
+    type Output = Self;
+
+    /// The negation of `self`.
+    fn not(self) -> Self {
+        Self::raw(format!("NOT ({})", self.sql))
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.sql)
+    }
+}
+
+impl From<Expr> for String {
+    fn from(expr: Expr) -> Self {
+        expr.sql
+    }
+}
+
+impl Column {
+    fn compare<L: Into<Literal>>(&self, op: &str, value: L) -> Expr {
+        Expr::raw(format!("{} {} {}", self, op, value.into()))
+    }
+
+    /// `self = value`, or `self IS NULL` if `value` is [`Literal::Null`](Literal::Null).
+    pub fn eq<L: Into<Literal>>(&self, value: L) -> Expr {
+        match value.into() {
+            Literal::Null => self.is_null(),
+            value => self.compare("=", value),
+        }
+    }
+
+    /// `self != value`, or `self IS NOT NULL` if `value` is [`Literal::Null`](Literal::Null).
+    pub fn ne<L: Into<Literal>>(&self, value: L) -> Expr {
+        match value.into() {
+            Literal::Null => self.is_not_null(),
+            value => self.compare("!=", value),
+        }
+    }
+
+    /// `self < value`.
+    pub fn lt<L: Into<Literal>>(&self, value: L) -> Expr {
+        self.compare("<", value)
+    }
+
+    /// `self <= value`.
+    pub fn le<L: Into<Literal>>(&self, value: L) -> Expr {
+        self.compare("<=", value)
+    }
+
+    /// `self > value`.
+    pub fn gt<L: Into<Literal>>(&self, value: L) -> Expr {
+        self.compare(">", value)
+    }
+
+    /// `self >= value`.
+    pub fn ge<L: Into<Literal>>(&self, value: L) -> Expr {
+        self.compare(">=", value)
+    }
+
+    /// `self BETWEEN low AND high`, bounds included.
+    pub fn between<L: Into<Literal>, H: Into<Literal>>(&self, low: L, high: H) -> Expr {
+        Expr::raw(format!(
+            "{} BETWEEN {} AND {}",
+            self,
+            low.into(),
+            high.into()
+        ))
+    }
+
+    /// `self IN (values...)`. Always false if `values` is empty.
+    pub fn is_in<I>(&self, values: I) -> Expr
+    where
+        I: IntoIterator,
+        I::Item: Into<Literal>,
+    {
+        let values: Vec<String> = values
+            .into_iter()
+            .map(|value| value.into().to_string())
+            .collect();
+        if values.is_empty() {
+            Expr::raw("FALSE")
+        } else {
+            Expr::raw(format!("{} IN ({})", self, values.join(", ")))
+        }
+    }
+
+    /// `self IS NULL`.
+    pub fn is_null(&self) -> Expr {
+        Expr::raw(format!("{} IS NULL", self))
+    }
+
+    /// `self IS NOT NULL`.
+    pub fn is_not_null(&self) -> Expr {
+        Expr::raw(format!("{} IS NOT NULL", self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn render_literals() {
+        assert_eq!(Literal::from(Option::<i64>::None).to_string(), "NULL");
+        assert_eq!(Literal::from(-5).to_string(), "-5");
+        assert_eq!(Literal::from(1.5).to_string(), "1.5");
+        assert_eq!(
+            Literal::from(f64::NAN).to_string(),
+            "CAST('nan' AS FLOAT64)"
+        );
+        assert_eq!(Literal::numeric("1.25").to_string(), "NUMERIC '1.25'");
+        assert_eq!(Literal::bignumeric("1e40").to_string(), "BIGNUMERIC '1e40'");
+        assert_eq!(
+            Literal::from("it's a \\ \"test\"\n\u{7}é").to_string(),
+            "'it\\'s a \\\\ \"test\"\\n\\x07é'"
+        );
+        assert_eq!(
+            Literal::from(&[0x61, 0xc3, 0xa9, 0x27][..]).to_string(),
+            "b'a\\xc3\\xa9\\''"
+        );
+        assert_eq!(
+            Literal::date(2014, 9, 27).unwrap().to_string(),
+            "DATE '2014-09-27'"
+        );
+
+        let time = UNIX_EPOCH + Duration::from_micros(1_704_067_200_123_456);
+        assert_eq!(
+            Literal::from(time).to_string(),
+            "TIMESTAMP '2024-01-01 00:00:00.123456+00'"
+        );
+        let time = UNIX_EPOCH - Duration::from_secs(1);
+        assert_eq!(
+            Literal::from(time).to_string(),
+            "TIMESTAMP '1969-12-31 23:59:59.000000+00'"
+        );
+    }
+
+    #[test]
+    fn reject_invalid_dates() {
+        assert!(Literal::date(2024, 2, 29).is_some());
+        assert!(Literal::date(2023, 2, 29).is_none());
+        assert!(Literal::date(1900, 2, 29).is_none());
+        assert!(Literal::date(2014, 13, 1).is_none());
+        assert!(Literal::date(2014, 4, 31).is_none());
+        assert!(Literal::date(2014, 9, 0).is_none());
+        assert!(Literal::date(0, 1, 1).is_none());
+        assert!(Literal::date(10_000, 1, 1).is_none());
+    }
+
+    #[test]
+    fn render_expressions() {
+        assert_eq!(
+            col("address.city").eq("Paris").to_string(),
+            "`address`.`city` = 'Paris'"
+        );
+        assert_eq!(col("a`b").is_not_null().to_string(), "`a\\`b` IS NOT NULL");
+        assert_eq!(col("a").eq(Literal::Null).to_string(), "`a` IS NULL");
+        assert_eq!(col("a").is_in(vec![1, 2]).to_string(), "`a` IN (1, 2)");
+        assert_eq!(col("a").is_in(Vec::<i64>::new()).to_string(), "FALSE");
+        assert_eq!(
+            (!col("a").lt(1).or(col("b").ge(2))).to_string(),
+            "NOT ((`a` < 1) OR (`b` >= 2))"
+        );
+    }
+}
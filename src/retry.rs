@@ -0,0 +1,184 @@
+//! Retrying transient failures of the BigQuery Storage API.
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::BuildHasher;
+use std::time::{Duration, Instant};
+
+use tonic::{Code, Status};
+
+use crate::{Error, ErrorDetails};
+
+/// Controls how calls to the BigQuery Storage API recover from transient failures.
+///
+/// Creating a read session and opening a stream are retried when they fail with a
+/// retryable status. When a `ReadRows` call fails while a
+/// [`RowsStreamReader`](crate::read::RowsStreamReader) is reading it, the call is re-issued
+/// from the offset of the last row delivered. Retries wait for an exponential backoff, or
+/// for the delay the server asks for in a `RetryInfo`, whichever is longer.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    jitter: f64,
+    deadline: Option<Duration>,
+    retryable_codes: Vec<Code>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: 0.2,
+            deadline: None,
+            retryable_codes: vec![Code::Unavailable, Code::ResourceExhausted],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create the default policy: up to 5 consecutive retries of `UNAVAILABLE` and
+    /// `RESOURCE_EXHAUSTED` failures, with a backoff starting at 100ms and doubling up to
+    /// 10s, shortened by up to 20% at random.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A policy that never retries.
+    pub fn never() -> Self {
+        Self::default().max_attempts(0)
+    }
+
+    /// Sets the maximum number of consecutive retries before giving up.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Sets the backoff before the first retry.
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Sets the upper bound of the backoff between two retries.
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Sets the factor, at least 1, by which the backoff grows after each retry.
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// Sets the fraction, between 0 and 1, by which each backoff is shortened at random,
+    /// so that clients failing at the same time do not retry at the same time.
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        // Unlike `max` and `min`, `clamp` keeps NaN
+        self.jitter = if jitter.is_nan() {
+            0.0
+        } else {
+            jitter.clamp(0.0, 1.0)
+        };
+        self
+    }
+
+    /// Sets the time after which a call is not retried anymore, counted from its first
+    /// attempt. Unbounded by default.
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets the status codes after which a call is retried. Connections reset by the
+    /// server, reported as `INTERNAL`, count as `UNAVAILABLE`.
+    pub fn retryable_codes(mut self, retryable_codes: Vec<Code>) -> Self {
+        self.retryable_codes = retryable_codes;
+        self
+    }
+
+    /// Whether a call failing with `status` should be retried under this policy.
+    pub(crate) fn should_retry(&self, status: &Status) -> bool {
+        let code = if is_connection_reset(status) {
+            Code::Unavailable
+        } else {
+            status.code()
+        };
+        self.retryable_codes.contains(&code)
+    }
+
+    /// The backoff before retrying `status`, the `attempt`-th consecutive failure (starting
+    /// at 0), `elapsed` after the first attempt. Returns `None` if `status` should not be
+    /// retried.
+    pub(crate) fn backoff(
+        &self,
+        status: &Status,
+        attempt: u32,
+        elapsed: Duration,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts || !self.should_retry(status) {
+            return None;
+        }
+        let backoff = self.initial_backoff.as_secs_f64() * self.multiplier.powi(attempt as i32);
+        let backoff = backoff.min(self.max_backoff.as_secs_f64());
+        let backoff = Duration::from_secs_f64(backoff * (1.0 - self.jitter * random()));
+        // The server knows better when the call can succeed
+        let backoff = match ErrorDetails::from_status(status).retry_delay() {
+            Some(retry_delay) => backoff.max(retry_delay),
+            None => backoff,
+        };
+        match self.deadline {
+            Some(deadline) if elapsed + backoff > deadline => None,
+            _ => Some(backoff),
+        }
+    }
+
+    /// Call `f` until it succeeds, fails with a status that should not be retried, or
+    /// this policy gives up.
+    pub(crate) async fn retry<T, F, Fut>(&self, mut f: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let start = Instant::now();
+        let mut attempt = 0;
+        loop {
+            let status = match f().await {
+                Err(Error::Status(status)) => status,
+                result => return result,
+            };
+            match self.backoff(&status, attempt, start.elapsed()) {
+                Some(backoff) => tokio::time::sleep(backoff).await,
+                None => return Err(status.into()),
+            }
+            attempt += 1;
+        }
+    }
+}
+
+/// A number between 0 and 1, random enough to spread retries.
+fn random() -> f64 {
+    let hash = RandomState::new().hash_one(Instant::now());
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Whether `status` is a transient failure, after which a call can safely be re-issued.
+pub(crate) fn is_retryable(status: &Status) -> bool {
+    status.code() == Code::Unavailable || is_connection_reset(status)
+}
+
+/// Whether `status` reports a connection reset by the server.
+fn is_connection_reset(status: &Status) -> bool {
+    // Connections reset by the server surface as INTERNAL
+    status.code() == Code::Internal && {
+        let message = status.message();
+        message.contains("RST_STREAM")
+            || message.contains("Received unexpected EOS on DATA frame from server")
+    }
+}
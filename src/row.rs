@@ -0,0 +1,397 @@
+//! Decoding Arrow record batches into Rust structs.
+//!
+//! [`BigQueryRow`](BigQueryRow) is implemented with `#[derive(BigQueryRow)]`, with the
+//! `derive` feature. Each field of the struct is read from the column of the same name,
+//! or of the name given with `#[bq(rename = "...")]`, and must implement
+//! [`FromColumn`](FromColumn). Nested structs deriving `BigQueryRow` are read from
+//! `STRUCT` columns, `Option`s from nullable columns and `Vec`s from `REPEATED` columns.
+//! ```rust,ignore
+//! use bigquery_storage::BigQueryRow;
+//!
+//! #[derive(BigQueryRow)]
+//! struct Station {
+//!     id: i64,
+//!     name: Option<String>,
+//!     #[bq(rename = "install_date")]
+//!     installed: Option<i32>,
+//! }
+//!
+//! let stations = client
+//!     .read_session_builder(table)
+//!     .read_as::<Station>()
+//!     .await?;
+//! ```
+use arrow::array::{
+    Array, ArrayRef, BooleanArray, Date32Array, Date64Array, Float32Array, Float64Array,
+    Int16Array, Int32Array, Int64Array, Int8Array, LargeListArray, LargeStringArray, ListArray,
+    StringArray, StructArray, Time32MillisecondArray, Time32SecondArray, Time64MicrosecondArray,
+    Time64NanosecondArray, TimestampMicrosecondArray, TimestampMillisecondArray,
+    TimestampNanosecondArray, TimestampSecondArray, UInt16Array, UInt32Array, UInt8Array,
+};
+use arrow::datatypes::{DataType, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use futures::stream::BoxStream;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::column_path;
+use crate::Error;
+
+/// A stream of rows decoded into `T`.
+pub type RowStream<T> = BoxStream<'static, Result<T, Error>>;
+
+/// A row of a table, decoded from Arrow record batches.
+pub trait BigQueryRow: Sized {
+    /// The fields to read from the table, see
+    /// [`ReadSessionBuilder::selected_fields`](crate::client::ReadSessionBuilder::selected_fields).
+    /// Fields of nested structs are selected by their path, e.g. `address.city`.
+    fn selected_fields() -> Vec<String>;
+
+    /// Decode the rows of `batch`.
+    fn from_record_batch(batch: &RecordBatch) -> Result<Vec<Self>, Error>;
+}
+
+/// A value read from a row of an Arrow column.
+pub trait FromColumn: Sized {
+    /// The paths of the fields to select under a column of this type, relative to the
+    /// column. Empty, to select the whole column, unless this is a struct, in which case
+    /// only these sub-fields of the column are selected.
+    fn nested_fields() -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Read the value at `row` of `array`.
+    fn from_column(array: &dyn Array, row: usize) -> Result<Self, ColumnError>;
+}
+
+/// A value that could not be read from a column.
+#[derive(Debug)]
+pub struct ColumnError {
+    pub(crate) column: String,
+    pub(crate) message: String,
+}
+
+impl ColumnError {
+    fn new<S: Into<String>>(message: S) -> Self {
+        Self {
+            column: String::new(),
+            message: message.into(),
+        }
+    }
+
+    /// The path of the column, e.g. `address.city` or `tags[2]`.
+    pub fn column(&self) -> &str {
+        &self.column
+    }
+
+    /// Prefix the column of this error with `segment`, a field name or a `[index]`.
+    fn in_column(mut self, segment: &str) -> Self {
+        self.column = column_path(segment, &self.column);
+        self
+    }
+}
+
+impl std::fmt::Display for ColumnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "column `{}`: {}", self.column, self.message)
+    }
+}
+
+impl std::error::Error for ColumnError {}
+
+/// The columns of a record batch or a struct column, by name. Used by the code
+/// generated by `#[derive(BigQueryRow)]`.
+#[doc(hidden)]
+pub struct Columns {
+    columns: Vec<(String, ArrayRef)>,
+}
+
+impl Columns {
+    pub fn from_record_batch(batch: &RecordBatch) -> Self {
+        let schema = batch.schema();
+        let columns = schema
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .zip(batch.columns().iter().cloned())
+            .collect();
+        Self { columns }
+    }
+
+    pub fn from_struct(array: &dyn Array, row: usize) -> Result<Self, ColumnError> {
+        check_not_null(array, row)?;
+        let fields = match array.data_type() {
+            DataType::Struct(fields) => fields,
+            other => return Err(mismatch::<Self>(other)),
+        };
+        let struct_array = downcast::<StructArray>(array)?;
+        let columns = fields
+            .iter()
+            .map(|field| field.name().clone())
+            .zip(struct_array.columns().into_iter().cloned())
+            .collect();
+        Ok(Self { columns })
+    }
+
+    pub fn get<T: FromColumn>(&self, name: &str, row: usize) -> Result<T, ColumnError> {
+        let (_, column) = self
+            .columns
+            .iter()
+            .find(|(column, _)| column == name)
+            .ok_or_else(|| ColumnError::new("no such column").in_column(name))?;
+        T::from_column(column.as_ref(), row).map_err(|e| e.in_column(name))
+    }
+}
+
+/// Add the fields to select for a column `name` of type `T` to `fields`, unless they
+/// already are. Used by the code generated by `#[derive(BigQueryRow)]`.
+#[doc(hidden)]
+pub fn push_selected_fields<T: FromColumn>(fields: &mut Vec<String>, name: &str) {
+    let nested = T::nested_fields();
+    let selected = if nested.is_empty() {
+        vec![name.to_string()]
+    } else {
+        nested
+            .into_iter()
+            .map(|field| format!("{}.{}", name, field))
+            .collect()
+    };
+    for field in selected {
+        if !fields.contains(&field) {
+            fields.push(field);
+        }
+    }
+}
+
+fn mismatch<T>(data_type: &DataType) -> ColumnError {
+    ColumnError::new(format!(
+        "cannot read {:?} as {}",
+        data_type,
+        std::any::type_name::<T>()
+    ))
+}
+
+fn check_not_null(array: &dyn Array, row: usize) -> Result<(), ColumnError> {
+    if array.is_null(row) {
+        Err(ColumnError::new(
+            "unexpected null, the field should be an Option",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Downcast `array` to the array type of its data type. Shared by the modules that
+/// read or write Arrow arrays.
+pub(crate) fn downcast<T: 'static>(array: &dyn Array) -> Result<&T, ColumnError> {
+    array
+        .as_any()
+        .downcast_ref::<T>()
+        .ok_or_else(|| mismatch::<T>(array.data_type()))
+}
+
+macro_rules! from_column {
+    { $(
+        $ty:ty { $($data_type:pat => $array:ty),+ $(,)? }
+    )* } => {
+        $(
+            impl FromColumn for $ty {
+                fn from_column(array: &dyn Array, row: usize) -> Result<Self, ColumnError> {
+                    check_not_null(array, row)?;
+                    match array.data_type() {
+                        $($data_type => Ok(downcast::<$array>(array)?.value(row).into()),)+
+                        other => Err(mismatch::<Self>(other)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+from_column! {
+    bool { DataType::Boolean => BooleanArray }
+    i32 {
+        DataType::Int8 => Int8Array,
+        DataType::Int16 => Int16Array,
+        DataType::Int32 => Int32Array,
+        DataType::UInt8 => UInt8Array,
+        DataType::UInt16 => UInt16Array,
+        DataType::Date32(_) => Date32Array,
+        DataType::Time32(TimeUnit::Second) => Time32SecondArray,
+        DataType::Time32(TimeUnit::Millisecond) => Time32MillisecondArray,
+    }
+    i64 {
+        DataType::Int8 => Int8Array,
+        DataType::Int16 => Int16Array,
+        DataType::Int32 => Int32Array,
+        DataType::Int64 => Int64Array,
+        DataType::UInt8 => UInt8Array,
+        DataType::UInt16 => UInt16Array,
+        DataType::UInt32 => UInt32Array,
+        DataType::Date32(_) => Date32Array,
+        DataType::Date64(_) => Date64Array,
+        DataType::Time64(TimeUnit::Microsecond) => Time64MicrosecondArray,
+        DataType::Time64(TimeUnit::Nanosecond) => Time64NanosecondArray,
+        DataType::Timestamp(TimeUnit::Second, _) => TimestampSecondArray,
+        DataType::Timestamp(TimeUnit::Millisecond, _) => TimestampMillisecondArray,
+        DataType::Timestamp(TimeUnit::Microsecond, _) => TimestampMicrosecondArray,
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => TimestampNanosecondArray,
+    }
+    f32 { DataType::Float32 => Float32Array }
+    f64 {
+        DataType::Float32 => Float32Array,
+        DataType::Float64 => Float64Array,
+    }
+    String {
+        DataType::Utf8 => StringArray,
+        DataType::LargeUtf8 => LargeStringArray,
+    }
+}
+
+impl FromColumn for SystemTime {
+    fn from_column(array: &dyn Array, row: usize) -> Result<Self, ColumnError> {
+        check_not_null(array, row)?;
+        let (value, from_units): (i64, fn(u64) -> Duration) = match array.data_type() {
+            DataType::Timestamp(TimeUnit::Second, _) => (
+                downcast::<TimestampSecondArray>(array)?.value(row),
+                Duration::from_secs,
+            ),
+            DataType::Timestamp(TimeUnit::Millisecond, _) => (
+                downcast::<TimestampMillisecondArray>(array)?.value(row),
+                Duration::from_millis,
+            ),
+            DataType::Timestamp(TimeUnit::Microsecond, _) => (
+                downcast::<TimestampMicrosecondArray>(array)?.value(row),
+                Duration::from_micros,
+            ),
+            DataType::Timestamp(TimeUnit::Nanosecond, _) => (
+                downcast::<TimestampNanosecondArray>(array)?.value(row),
+                Duration::from_nanos,
+            ),
+            other => return Err(mismatch::<Self>(other)),
+        };
+        let since_epoch = from_units(value.unsigned_abs());
+        let time = if value < 0 {
+            UNIX_EPOCH.checked_sub(since_epoch)
+        } else {
+            UNIX_EPOCH.checked_add(since_epoch)
+        };
+        time.ok_or_else(|| ColumnError::new("timestamp out of range"))
+    }
+}
+
+impl<T: FromColumn> FromColumn for Option<T> {
+    fn nested_fields() -> Vec<String> {
+        T::nested_fields()
+    }
+
+    fn from_column(array: &dyn Array, row: usize) -> Result<Self, ColumnError> {
+        if array.is_null(row) {
+            Ok(None)
+        } else {
+            T::from_column(array, row).map(Some)
+        }
+    }
+}
+
+impl<T: FromColumn> FromColumn for Vec<T> {
+    fn nested_fields() -> Vec<String> {
+        T::nested_fields()
+    }
+
+    fn from_column(array: &dyn Array, row: usize) -> Result<Self, ColumnError> {
+        // BigQuery reads empty REPEATED fields as nulls
+        if array.is_null(row) {
+            return Ok(Vec::new());
+        }
+        let items = match array.data_type() {
+            DataType::List(_) => downcast::<ListArray>(array)?.value(row),
+            DataType::LargeList(_) => downcast::<LargeListArray>(array)?.value(row),
+            other => return Err(mismatch::<Self>(other)),
+        };
+        (0..items.len())
+            .map(|i| {
+                T::from_column(items.as_ref(), i).map_err(|e| e.in_column(&format!("[{}]", i)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow::array::Int64Array;
+    use arrow::datatypes::{Field, Schema};
+
+    use std::sync::Arc;
+
+    struct Row {
+        id: i64,
+        name: Option<String>,
+    }
+
+    impl BigQueryRow for Row {
+        fn selected_fields() -> Vec<String> {
+            let mut fields = Vec::new();
+            push_selected_fields::<i64>(&mut fields, "id");
+            push_selected_fields::<Option<String>>(&mut fields, "name");
+            fields
+        }
+
+        fn from_record_batch(batch: &RecordBatch) -> Result<Vec<Self>, Error> {
+            let columns = Columns::from_record_batch(batch);
+            (0..batch.num_rows())
+                .map(|row| {
+                    Result::<Self, Error>::Ok(Self {
+                        id: columns.get("id", row)?,
+                        name: columns.get("name", row)?,
+                    })
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn decode_rows() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![Some(1), None])),
+                Arc::new(StringArray::from(vec![Some("a"), None])),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(Row::selected_fields(), vec!["id", "name"]);
+
+        let columns = Columns::from_record_batch(&batch);
+        let first: Row = Row {
+            id: columns.get("id", 0).unwrap(),
+            name: columns.get("name", 0).unwrap(),
+        };
+        assert_eq!((first.id, first.name.as_deref()), (1, Some("a")));
+
+        match Row::from_record_batch(&batch) {
+            Err(Error::Column(e)) => assert_eq!(e.column(), "id"),
+            _ => panic!("expected the null id to be rejected"),
+        }
+    }
+
+    #[test]
+    fn decode_timestamps() {
+        // 9999-12-31, the last day BigQuery allows
+        let last_day = Duration::from_secs(253_402_214_400);
+        let micros = TimestampMicrosecondArray::from_vec(vec![last_day.as_micros() as i64], None);
+        let time = SystemTime::from_column(&micros, 0).unwrap();
+        assert_eq!(time, UNIX_EPOCH + last_day);
+
+        let seconds = TimestampSecondArray::from_vec(vec![-1], None);
+        let time = SystemTime::from_column(&seconds, 0).unwrap();
+        assert_eq!(time, UNIX_EPOCH - Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,373 @@
+//! The schema of a read session, independent of its data format.
+//!
+//! [`ReadSession::schema`](crate::client::ReadSession::schema) describes the columns of
+//! a session as BigQuery types and modes, whether its rows are serialized with Arrow or
+//! Avro, so downstream work can be planned before any data is downloaded.
+use serde_json::Value as JsonValue;
+
+use std::convert::TryFrom;
+
+use crate::{Error, SerializedSchema};
+
+#[cfg(feature = "arrow")]
+use arrow::datatypes::{DataType, Field as ArrowField};
+
+/// The type of a column, as in a BigQuery table schema.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FieldType {
+    String,
+    Bytes,
+    Int64,
+    Float64,
+    Numeric,
+    BigNumeric,
+    Bool,
+    Timestamp,
+    Date,
+    Time,
+    DateTime,
+    Geography,
+    Json,
+    Interval,
+    /// A `STRUCT` (or `RECORD`), see [`SchemaField::fields`](SchemaField::fields).
+    Struct,
+}
+
+/// Whether a column holds one value, an optional value or an array of values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FieldMode {
+    Nullable,
+    Required,
+    Repeated,
+}
+
+/// A column of a [`SessionSchema`](SessionSchema), or a field of a `STRUCT` column.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaField {
+    name: String,
+    field_type: FieldType,
+    mode: FieldMode,
+    fields: Vec<SchemaField>,
+}
+
+impl SchemaField {
+    /// The name of the column.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The type of the column, or of its elements if it is `REPEATED`.
+    pub fn field_type(&self) -> FieldType {
+        self.field_type
+    }
+
+    /// The mode of the column.
+    pub fn mode(&self) -> FieldMode {
+        self.mode
+    }
+
+    /// The fields of a `STRUCT` column. Empty for other types.
+    pub fn fields(&self) -> &[SchemaField] {
+        &self.fields
+    }
+}
+
+/// The columns read by a session.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SessionSchema {
+    fields: Vec<SchemaField>,
+}
+
+impl SessionSchema {
+    /// The columns, in the order they are read.
+    pub fn fields(&self) -> &[SchemaField] {
+        &self.fields
+    }
+
+    /// The column at `path`, where fields of `STRUCT` columns are separated by dots,
+    /// e.g. `address.city`.
+    pub fn field(&self, path: &str) -> Option<&SchemaField> {
+        let mut fields = self.fields.as_slice();
+        let mut found = None;
+        for name in path.split('.') {
+            let field = fields.iter().find(|field| field.name == name)?;
+            fields = &field.fields;
+            found = Some(field);
+        }
+        found
+    }
+}
+
+impl TryFrom<&SerializedSchema> for SessionSchema {
+    type Error = Error;
+
+    fn try_from(schema: &SerializedSchema) -> Result<Self, Self::Error> {
+        match schema {
+            SerializedSchema::Arrow(serialized_schema) => from_arrow(serialized_schema),
+            SerializedSchema::Avro(schema) => from_avro(schema),
+        }
+    }
+}
+
+#[cfg(feature = "arrow")]
+fn from_arrow(serialized_schema: &[u8]) -> Result<SessionSchema, Error> {
+    use arrow::ipc::reader::StreamReader;
+    use std::io::Cursor;
+
+    let reader = StreamReader::try_new(Cursor::new(serialized_schema))?;
+    let fields = reader
+        .schema()
+        .fields()
+        .iter()
+        .map(arrow_field)
+        .collect::<Result<_, _>>()?;
+    Ok(SessionSchema { fields })
+}
+
+#[cfg(not(feature = "arrow"))]
+fn from_arrow(_: &[u8]) -> Result<SessionSchema, Error> {
+    Err(Error::invalid(
+        "the `arrow` feature is required to read the schema of an Arrow session",
+    ))
+}
+
+#[cfg(feature = "arrow")]
+fn arrow_field(field: &ArrowField) -> Result<SchemaField, Error> {
+    let (data_type, mode) = match field.data_type() {
+        DataType::List(item) | DataType::LargeList(item) => (item.data_type(), FieldMode::Repeated),
+        data_type if field.is_nullable() => (data_type, FieldMode::Nullable),
+        data_type => (data_type, FieldMode::Required),
+    };
+
+    // Some types are only told apart by the extension name BigQuery sets in the
+    // metadata of the field
+    let extension = field
+        .metadata()
+        .as_ref()
+        .and_then(|metadata| metadata.get("ARROW:extension:name"))
+        .map(String::as_str);
+
+    let mut fields = Vec::new();
+    let field_type = match (data_type, extension) {
+        (_, Some("google:sqlType:geography")) => FieldType::Geography,
+        (_, Some("google:sqlType:json")) => FieldType::Json,
+        (_, Some("google:sqlType:datetime")) => FieldType::DateTime,
+        (DataType::Utf8, _) | (DataType::LargeUtf8, _) => FieldType::String,
+        (DataType::Binary, _) | (DataType::LargeBinary, _) => FieldType::Bytes,
+        (DataType::Int64, _) => FieldType::Int64,
+        (DataType::Float64, _) => FieldType::Float64,
+        (DataType::Boolean, _) => FieldType::Bool,
+        (DataType::Decimal(precision, _), _) if *precision <= 38 => FieldType::Numeric,
+        (DataType::Decimal(_, _), _) => FieldType::BigNumeric,
+        (DataType::Date32(_), _) => FieldType::Date,
+        (DataType::Time64(_), _) => FieldType::Time,
+        (DataType::Timestamp(_, Some(_)), _) => FieldType::Timestamp,
+        (DataType::Timestamp(_, None), _) => FieldType::DateTime,
+        (DataType::Interval(_), _) => FieldType::Interval,
+        (DataType::Struct(struct_fields), _) => {
+            fields = struct_fields
+                .iter()
+                .map(arrow_field)
+                .collect::<Result<_, _>>()?;
+            FieldType::Struct
+        }
+        (other, _) => {
+            return Err(Error::invalid(format!(
+                "unsupported arrow type {:?} for field `{}`",
+                other,
+                field.name()
+            )))
+        }
+    };
+
+    Ok(SchemaField {
+        name: field.name().clone(),
+        field_type,
+        mode,
+        fields,
+    })
+}
+
+fn from_avro(schema: &str) -> Result<SessionSchema, Error> {
+    let schema: JsonValue = serde_json::from_str(schema)?;
+    let fields = avro_record_fields(&schema)?;
+    Ok(SessionSchema { fields })
+}
+
+fn avro_record_fields(record: &JsonValue) -> Result<Vec<SchemaField>, Error> {
+    record
+        .get("fields")
+        .and_then(JsonValue::as_array)
+        .ok_or_else(|| Error::invalid("expected an avro record with fields"))?
+        .iter()
+        .map(avro_field)
+        .collect()
+}
+
+fn avro_field(field: &JsonValue) -> Result<SchemaField, Error> {
+    let name = field
+        .get("name")
+        .and_then(JsonValue::as_str)
+        .ok_or_else(|| Error::invalid("expected an avro field with a name"))?;
+    let invalid = |ty: &JsonValue| {
+        Error::invalid(format!("unsupported avro type {} for field `{}`", ty, name))
+    };
+
+    let mut ty = field
+        .get("type")
+        .ok_or_else(|| Error::invalid(format!("avro field `{}` has no type", name)))?;
+
+    // Nullable fields are unions with null, and repeated fields arrays
+    let mut mode = FieldMode::Required;
+    if let Some(variants) = ty.as_array() {
+        let mut non_null = variants
+            .iter()
+            .filter(|variant| variant.as_str() != Some("null"));
+        match (non_null.next(), non_null.next()) {
+            (Some(variant), None) if variants.len() == 2 => {
+                ty = variant;
+                mode = FieldMode::Nullable;
+            }
+            _ => return Err(invalid(ty)),
+        }
+    }
+    if ty.get("type").and_then(JsonValue::as_str) == Some("array") {
+        ty = ty.get("items").ok_or_else(|| invalid(ty))?;
+        mode = FieldMode::Repeated;
+    }
+
+    let (base, logical_type, sql_type) = match ty {
+        JsonValue::String(base) => (base.as_str(), None, None),
+        JsonValue::Object(object) => (
+            object
+                .get("type")
+                .and_then(JsonValue::as_str)
+                .ok_or_else(|| invalid(ty))?,
+            object.get("logicalType").and_then(JsonValue::as_str),
+            object.get("sqlType").and_then(JsonValue::as_str),
+        ),
+        _ => return Err(invalid(ty)),
+    };
+
+    let mut fields = Vec::new();
+    let field_type = match (base, logical_type, sql_type) {
+        (_, _, Some("GEOGRAPHY")) => FieldType::Geography,
+        (_, _, Some("JSON")) => FieldType::Json,
+        (_, _, Some("DATETIME")) => FieldType::DateTime,
+        (_, _, Some("INTERVAL")) => FieldType::Interval,
+        ("string", _, _) => FieldType::String,
+        ("bytes", Some("decimal"), _) => match ty.get("precision").and_then(JsonValue::as_u64) {
+            Some(precision) if precision > 38 => FieldType::BigNumeric,
+            _ => FieldType::Numeric,
+        },
+        ("bytes", _, _) => FieldType::Bytes,
+        ("long", Some("timestamp-micros"), _) => FieldType::Timestamp,
+        ("long", Some("local-timestamp-micros"), _) => FieldType::DateTime,
+        ("long", Some("time-micros"), _) => FieldType::Time,
+        ("long", _, _) => FieldType::Int64,
+        ("int", Some("date"), _) => FieldType::Date,
+        ("double", _, _) => FieldType::Float64,
+        ("boolean", _, _) => FieldType::Bool,
+        ("record", _, _) => {
+            fields = avro_record_fields(ty)?;
+            FieldType::Struct
+        }
+        _ => return Err(invalid(ty)),
+    };
+
+    Ok(SchemaField {
+        name: name.to_string(),
+        field_type,
+        mode,
+        fields,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expected() -> SessionSchema {
+        let field = |name: &str, field_type, mode, fields| SchemaField {
+            name: name.to_string(),
+            field_type,
+            mode,
+            fields,
+        };
+        SessionSchema {
+            fields: vec![
+                field("id", FieldType::Int64, FieldMode::Required, vec![]),
+                field("name", FieldType::String, FieldMode::Nullable, vec![]),
+                field("tags", FieldType::String, FieldMode::Repeated, vec![]),
+                field(
+                    "address",
+                    FieldType::Struct,
+                    FieldMode::Nullable,
+                    vec![
+                        field("city", FieldType::String, FieldMode::Nullable, vec![]),
+                        field("since", FieldType::Date, FieldMode::Nullable, vec![]),
+                    ],
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn schema_from_avro() {
+        let avro = r#"{
+            "type": "record",
+            "name": "__root__",
+            "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "name", "type": ["null", "string"]},
+                {"name": "tags", "type": {"type": "array", "items": "string"}},
+                {"name": "address", "type": ["null", {
+                    "type": "record",
+                    "name": "__address",
+                    "fields": [
+                        {"name": "city", "type": ["null", "string"]},
+                        {"name": "since", "type": ["null", {"type": "int", "logicalType": "date"}]}
+                    ]
+                }]}
+            ]
+        }"#;
+
+        let schema = SessionSchema::try_from(&SerializedSchema::Avro(avro.to_string())).unwrap();
+        assert_eq!(schema, expected());
+        assert_eq!(
+            schema.field("address.since").map(SchemaField::field_type),
+            Some(FieldType::Date)
+        );
+        assert!(schema.field("address.country").is_none());
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn schema_from_arrow() {
+        use arrow::datatypes::{DateUnit, Field, Schema};
+        use arrow::ipc::writer::StreamWriter;
+
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+            Field::new(
+                "tags",
+                DataType::List(Box::new(Field::new("item", DataType::Utf8, true))),
+                false,
+            ),
+            Field::new(
+                "address",
+                DataType::Struct(vec![
+                    Field::new("city", DataType::Utf8, true),
+                    Field::new("since", DataType::Date32(DateUnit::Day), true),
+                ]),
+                true,
+            ),
+        ]);
+        let mut serialized_schema = Vec::new();
+        StreamWriter::try_new(&mut serialized_schema, &schema).unwrap();
+
+        let schema = SessionSchema::try_from(&SerializedSchema::Arrow(serialized_schema)).unwrap();
+        assert_eq!(schema, expected());
+    }
+}
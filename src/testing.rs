@@ -0,0 +1,995 @@
+//! An in-process fake of the BigQuery Storage API, for hermetic tests.
+//!
+//! A [`FakeServer`](FakeServer) serves tables seeded from Arrow
+//! [RecordBatch](arrow::record_batch::RecordBatch)es or Avro values, over plaintext HTTP/2
+//! on a local port. Rows appended through the Write API are kept as they were
+//! serialized, see [`FakeServer::written_rows`](FakeServer::written_rows). [`FakeServer::client`](FakeServer::client) returns a
+//! [`Client`](crate::client::Client) connected to it, which does not authenticate.
+//!
+//! # Example
+//! ```rust
+//! use std::sync::Arc;
+//!
+//! use arrow::array::Int64Array;
+//! use arrow::datatypes::{DataType, Field, Schema};
+//! use arrow::record_batch::RecordBatch;
+//! use bigquery_storage::testing::FakeServer;
+//! use bigquery_storage::Table;
+//!
+//! #[tokio::main(flavor = "current_thread")]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+//!     let batch = RecordBatch::try_new(
+//!         schema.clone(),
+//!         vec![Arc::new(Int64Array::from(vec![1, 2, 3]))],
+//!     )?;
+//!
+//!     let server = FakeServer::builder()
+//!         .arrow_table(Table::new("project", "dataset", "table"), schema, vec![batch])?
+//!         .start()
+//!         .await?;
+//!
+//!     let client = server.client().await?;
+//!     let read_session = client
+//!         .read_session_builder(Table::new("project", "dataset", "table"))
+//!         .build()
+//!         .await?;
+//!
+//!     Ok(())
+//! }
+//! ```
+use bytes::Bytes;
+use futures::channel::mpsc;
+use futures::stream::{self, Stream, StreamExt};
+use prost::Message;
+use prost_types::Any;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::{Endpoint, Server};
+use tonic::{Code, Request, Response, Status, Streaming};
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::googleapis::big_query_read_server::{BigQueryRead, BigQueryReadServer};
+use crate::googleapis::big_query_write_server::{BigQueryWrite, BigQueryWriteServer};
+use crate::googleapis::google::rpc::{RetryInfo, Status as RpcStatus};
+use crate::googleapis::{
+    append_rows_request, append_rows_response, read_rows_response::Rows,
+    read_session::TableReadOptions, storage_error::StorageErrorCode, stream_stats::Progress,
+    write_stream::Type as WriteStreamType, AppendRowsRequest, AppendRowsResponse,
+    BatchCommitWriteStreamsRequest, BatchCommitWriteStreamsResponse, CreateReadSessionRequest,
+    CreateWriteStreamRequest, FinalizeWriteStreamRequest, FinalizeWriteStreamResponse,
+    FlushRowsRequest, FlushRowsResponse, GetWriteStreamRequest, ReadRowsRequest, ReadRowsResponse,
+    ReadSession as BigQueryReadSession, ReadStream, SplitReadStreamRequest,
+    SplitReadStreamResponse, StorageError, StreamStats, ThrottleState, WriteStream,
+};
+use crate::handle::system_time_to_timestamp;
+use crate::{Client, Error, SerializedSchema, Table};
+
+#[cfg(feature = "arrow")]
+use crate::googleapis::ArrowRecordBatch;
+#[cfg(feature = "arrow")]
+use arrow::datatypes::{Schema as ArrowSchemaDef, SchemaRef};
+#[cfg(feature = "arrow")]
+use arrow::ipc::{
+    writer::{IpcWriteOptions, StreamWriter},
+    MetadataVersion,
+};
+#[cfg(feature = "arrow")]
+use arrow::record_batch::RecordBatch;
+
+#[cfg(feature = "avro")]
+use crate::googleapis::AvroRows;
+#[cfg(feature = "avro")]
+use avro_rs::types::Value as AvroValue;
+
+/// How long sessions created by the fake server are valid for.
+const SESSION_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// One `ReadRowsResponse` worth of rows.
+#[derive(Clone)]
+struct FakeMessage {
+    rows: Rows,
+    row_count: i64,
+}
+
+#[derive(Clone)]
+struct FakeTable {
+    schema: SerializedSchema,
+    messages: Vec<FakeMessage>,
+}
+
+struct FakeStream {
+    table: String,
+    /// The number of the session the stream belongs to, starting at 1.
+    session: usize,
+    messages: Vec<usize>,
+    failed: bool,
+}
+
+struct FakeWriteStream {
+    table: String,
+    stream_type: WriteStreamType,
+    rows: Vec<Vec<u8>>,
+    /// The number of rows visible to readers.
+    visible: usize,
+    finalized: bool,
+}
+
+#[derive(Default)]
+struct State {
+    tables: HashMap<String, FakeTable>,
+    streams: HashMap<String, FakeStream>,
+    num_sessions: usize,
+    /// The read options of the sessions created so far, in order.
+    read_options: Vec<TableReadOptions>,
+    num_splits: usize,
+    unavailable_after: Option<usize>,
+    stall_after: Option<usize>,
+    expired_sessions: usize,
+    exhausted_quota: Option<(usize, Duration)>,
+    /// The number of calls to `FinalizeWriteStream` that succeed, and the number made.
+    finalize_quota: Option<usize>,
+    num_finalized: usize,
+    write_streams: HashMap<String, FakeWriteStream>,
+    num_write_streams: usize,
+    written: HashMap<String, Vec<Vec<u8>>>,
+}
+
+impl State {
+    fn create_read_session(
+        &mut self,
+        req: CreateReadSessionRequest,
+    ) -> Result<BigQueryReadSession, Status> {
+        let CreateReadSessionRequest {
+            parent,
+            read_session,
+            max_stream_count,
+            ..
+        } = req;
+
+        if let Some((calls, retry_delay)) = self.exhausted_quota.as_mut() {
+            if *calls > 0 {
+                *calls -= 1;
+                return Err(quota_exceeded(*retry_delay));
+            }
+        }
+
+        let mut read_session =
+            read_session.ok_or_else(|| Status::invalid_argument("missing read session"))?;
+        let table = self
+            .tables
+            .get(&read_session.table)
+            .cloned()
+            .ok_or_else(|| Status::not_found(format!("table {} not found", read_session.table)))?;
+        if read_session.data_format() != table.schema.data_format() {
+            return Err(Status::invalid_argument(format!(
+                "table {} is only served as {:?}",
+                read_session.table,
+                table.schema.data_format()
+            )));
+        }
+
+        self.num_sessions += 1;
+        let name = format!("{}/locations/us/sessions/{}", parent, self.num_sessions);
+        self.read_options
+            .push(read_session.read_options.clone().unwrap_or_default());
+
+        let num_messages = table.messages.len();
+        let num_streams = match max_stream_count {
+            n if n > 0 => (n as usize).min(num_messages),
+            _ => num_messages,
+        }
+        .max(1);
+
+        let mut streams = Vec::with_capacity(num_streams);
+        for i in 0..num_streams {
+            let stream = format!("{}/streams/{}", name, i);
+            let messages =
+                (i * num_messages / num_streams..(i + 1) * num_messages / num_streams).collect();
+            self.streams.insert(
+                stream.clone(),
+                FakeStream {
+                    table: read_session.table.clone(),
+                    session: self.num_sessions,
+                    messages,
+                    failed: false,
+                },
+            );
+            streams.push(ReadStream { name: stream });
+        }
+
+        read_session.name = name;
+        read_session.schema = Some(table.schema.into());
+        read_session.streams = streams;
+        let expire_time = if self.num_sessions <= self.expired_sessions {
+            SystemTime::now() - Duration::from_secs(1)
+        } else {
+            SystemTime::now() + SESSION_TTL
+        };
+        read_session.expire_time = Some(system_time_to_timestamp(expire_time));
+        Ok(read_session)
+    }
+
+    /// The responses to `req`, and whether the stream then stalls instead of ending.
+    fn read_rows(
+        &mut self,
+        req: ReadRowsRequest,
+    ) -> Result<(Vec<Result<ReadRowsResponse, Status>>, bool), Status> {
+        let ReadRowsRequest {
+            read_stream,
+            offset,
+        } = req;
+
+        let fake_stream = self
+            .streams
+            .get_mut(&read_stream)
+            .ok_or_else(|| Status::not_found(format!("stream {} not found", read_stream)))?;
+        if fake_stream.session <= self.expired_sessions {
+            return Err(Status::failed_precondition(format!(
+                "read session of stream {} has expired",
+                read_stream
+            )));
+        }
+        let table = &self.tables[&fake_stream.table];
+
+        let mut start = 0;
+        let mut skipped = 0;
+        while skipped < offset && start < fake_stream.messages.len() {
+            skipped += table.messages[fake_stream.messages[start]].row_count;
+            start += 1;
+        }
+        if skipped != offset {
+            return Err(Status::out_of_range(format!(
+                "offset {} is not the start of a message of stream {}",
+                offset, read_stream
+            )));
+        }
+
+        let num_messages = fake_stream.messages.len();
+        let mut responses = Vec::with_capacity(num_messages - start);
+        let mut stalled = false;
+        for i in start..num_messages {
+            if self.stall_after == Some(i) {
+                stalled = true;
+                break;
+            }
+            if self.unavailable_after == Some(i) && !fake_stream.failed {
+                fake_stream.failed = true;
+                responses.push(Err(Status::unavailable("injected failure")));
+                break;
+            }
+
+            let FakeMessage { rows, row_count } = table.messages[fake_stream.messages[i]].clone();
+            let progress = Progress {
+                at_response_start: i as f64 / num_messages as f64,
+                at_response_end: (i + 1) as f64 / num_messages as f64,
+            };
+            responses.push(Ok(ReadRowsResponse {
+                rows: Some(rows),
+                row_count,
+                stats: Some(StreamStats {
+                    progress: Some(progress),
+                }),
+                throttle_state: Some(ThrottleState {
+                    throttle_percent: 0,
+                }),
+                ..Default::default()
+            }));
+        }
+
+        Ok((responses, stalled))
+    }
+
+    fn split_read_stream(
+        &mut self,
+        req: SplitReadStreamRequest,
+    ) -> Result<SplitReadStreamResponse, Status> {
+        let SplitReadStreamRequest { name, fraction } = req;
+        if !(fraction > 0.0 && fraction < 1.0) {
+            return Err(Status::invalid_argument(
+                "fraction must be strictly between 0 and 1",
+            ));
+        }
+
+        let fake_stream = self
+            .streams
+            .get_mut(&name)
+            .ok_or_else(|| Status::not_found(format!("stream {} not found", name)))?;
+        let at = (fake_stream.messages.len() as f64 * fraction) as usize;
+        if at == 0 || at >= fake_stream.messages.len() {
+            return Ok(SplitReadStreamResponse::default());
+        }
+
+        // Like the actual API, the original stream remains readable in full
+        let primary = fake_stream.messages[..at].to_vec();
+        let remainder = fake_stream.messages[at..].to_vec();
+        let table = fake_stream.table.clone();
+        let session = fake_stream.session;
+
+        self.num_splits += 1;
+        let primary_name = format!("{}/splits/{}/primary", name, self.num_splits);
+        let remainder_name = format!("{}/splits/{}/remainder", name, self.num_splits);
+        for (name, messages) in [(&primary_name, primary), (&remainder_name, remainder)] {
+            self.streams.insert(
+                name.clone(),
+                FakeStream {
+                    table: table.clone(),
+                    session,
+                    messages,
+                    failed: false,
+                },
+            );
+        }
+
+        Ok(SplitReadStreamResponse {
+            primary_stream: Some(ReadStream { name: primary_name }),
+            remainder_stream: Some(ReadStream {
+                name: remainder_name,
+            }),
+        })
+    }
+
+    /// Handle one request of an `AppendRows` call to `write_stream`. Errors are
+    /// reported in the response by the caller, like the actual API does.
+    fn append_rows(
+        &mut self,
+        write_stream: &str,
+        first: bool,
+        req: AppendRowsRequest,
+    ) -> Result<AppendRowsResponse, Status> {
+        let proto_data = match req.rows {
+            Some(append_rows_request::Rows::ProtoRows(proto_data)) => proto_data,
+            _ => return Err(Status::invalid_argument("missing proto rows")),
+        };
+        if first && proto_data.writer_schema.is_none() {
+            return Err(Status::invalid_argument(
+                "the first request of a call must carry the writer schema",
+            ));
+        }
+        let rows = proto_data.rows.unwrap_or_default().serialized_rows;
+
+        if let Some(table) = write_stream.strip_suffix("/streams/_default") {
+            if req.offset.is_some() {
+                return Err(Status::invalid_argument(
+                    "offsets are not supported by the default stream",
+                ));
+            }
+            self.written
+                .entry(table.to_string())
+                .or_default()
+                .extend(rows);
+            return Ok(append_rows_result(write_stream, None));
+        }
+
+        let fake_stream = self
+            .write_streams
+            .get_mut(write_stream)
+            .ok_or_else(|| Status::not_found(format!("stream {} not found", write_stream)))?;
+        if fake_stream.finalized {
+            return Err(Status::failed_precondition(format!(
+                "stream {} is finalized",
+                write_stream
+            )));
+        }
+
+        let start = fake_stream.rows.len() as i64;
+        match req.offset {
+            Some(offset) if offset < start => {
+                return Err(Status::already_exists(format!(
+                    "offset {} was already written",
+                    offset
+                )))
+            }
+            Some(offset) if offset > start => {
+                return Err(Status::out_of_range(format!(
+                    "offset {} is past the end of the stream, at {}",
+                    offset, start
+                )))
+            }
+            _ => {}
+        }
+        fake_stream.rows.extend(rows);
+        if fake_stream.stream_type == WriteStreamType::Committed {
+            self.make_visible(write_stream, None);
+        }
+
+        Ok(append_rows_result(write_stream, Some(start)))
+    }
+
+    /// Make the rows of `write_stream` visible in its table, up to `offset` included
+    /// or all of them.
+    fn make_visible(&mut self, write_stream: &str, offset: Option<i64>) {
+        let fake_stream = self.write_streams.get_mut(write_stream).unwrap();
+        let end = match offset {
+            // Flushing an empty stream makes no row visible, at offset -1
+            Some(offset) => usize::try_from(offset + 1)
+                .unwrap_or(0)
+                .min(fake_stream.rows.len()),
+            None => fake_stream.rows.len(),
+        };
+        if end > fake_stream.visible {
+            self.written
+                .entry(fake_stream.table.clone())
+                .or_default()
+                .extend(fake_stream.rows[fake_stream.visible..end].iter().cloned());
+            fake_stream.visible = end;
+        }
+    }
+
+    fn create_write_stream(
+        &mut self,
+        req: CreateWriteStreamRequest,
+    ) -> Result<WriteStream, Status> {
+        let CreateWriteStreamRequest {
+            parent,
+            write_stream,
+        } = req;
+        let mut write_stream =
+            write_stream.ok_or_else(|| Status::invalid_argument("missing write stream"))?;
+        let stream_type = write_stream.r#type();
+        if stream_type == WriteStreamType::Unspecified {
+            return Err(Status::invalid_argument("missing write stream type"));
+        }
+
+        self.num_write_streams += 1;
+        let name = format!("{}/streams/{}", parent, self.num_write_streams);
+        self.write_streams.insert(
+            name.clone(),
+            FakeWriteStream {
+                table: parent,
+                stream_type,
+                rows: Vec::new(),
+                visible: 0,
+                finalized: false,
+            },
+        );
+
+        write_stream.name = name;
+        write_stream.create_time = Some(system_time_to_timestamp(SystemTime::now()));
+        Ok(write_stream)
+    }
+
+    fn finalize_write_stream(
+        &mut self,
+        req: FinalizeWriteStreamRequest,
+    ) -> Result<FinalizeWriteStreamResponse, Status> {
+        self.num_finalized += 1;
+        if let Some(calls) = self.finalize_quota {
+            if self.num_finalized > calls {
+                return Err(Status::internal(format!("failed to finalize {}", req.name)));
+            }
+        }
+        let fake_stream = self
+            .write_streams
+            .get_mut(&req.name)
+            .ok_or_else(|| Status::not_found(format!("stream {} not found", req.name)))?;
+        fake_stream.finalized = true;
+        Ok(FinalizeWriteStreamResponse {
+            row_count: fake_stream.rows.len() as i64,
+        })
+    }
+
+    fn flush_rows(&mut self, req: FlushRowsRequest) -> Result<FlushRowsResponse, Status> {
+        let FlushRowsRequest {
+            write_stream,
+            offset,
+        } = req;
+        let fake_stream = self
+            .write_streams
+            .get(&write_stream)
+            .ok_or_else(|| Status::not_found(format!("stream {} not found", write_stream)))?;
+        if fake_stream.stream_type != WriteStreamType::Buffered {
+            return Err(Status::invalid_argument(
+                "only BUFFERED streams can be flushed",
+            ));
+        }
+        let offset = offset.unwrap_or(fake_stream.rows.len() as i64 - 1);
+        if offset >= fake_stream.rows.len() as i64 {
+            return Err(Status::out_of_range(format!(
+                "offset {} is past the end of the stream",
+                offset
+            )));
+        }
+
+        self.make_visible(&write_stream, Some(offset));
+        Ok(FlushRowsResponse { offset })
+    }
+
+    fn batch_commit_write_streams(
+        &mut self,
+        req: BatchCommitWriteStreamsRequest,
+    ) -> Result<BatchCommitWriteStreamsResponse, Status> {
+        let BatchCommitWriteStreamsRequest {
+            parent,
+            write_streams,
+        } = req;
+
+        let mut stream_errors = Vec::new();
+        for name in write_streams.iter() {
+            let (code, message) = match self.write_streams.get(name) {
+                None => (StorageErrorCode::StreamNotFound, "stream not found"),
+                Some(fake_stream) if fake_stream.table != parent => {
+                    (StorageErrorCode::StreamNotFound, "stream of another table")
+                }
+                Some(fake_stream) if fake_stream.stream_type != WriteStreamType::Pending => {
+                    (StorageErrorCode::InvalidStreamType, "stream is not PENDING")
+                }
+                Some(fake_stream) if !fake_stream.finalized => (
+                    StorageErrorCode::InvalidStreamState,
+                    "stream is not finalized",
+                ),
+                Some(fake_stream) if fake_stream.visible > 0 => (
+                    StorageErrorCode::StreamAlreadyCommitted,
+                    "stream is already committed",
+                ),
+                Some(_) => continue,
+            };
+            stream_errors.push(StorageError {
+                code: code as i32,
+                entity: name.clone(),
+                error_message: message.to_string(),
+            });
+        }
+
+        if !stream_errors.is_empty() {
+            return Ok(BatchCommitWriteStreamsResponse {
+                commit_time: None,
+                stream_errors,
+            });
+        }
+        for name in write_streams.iter() {
+            self.make_visible(name, None);
+        }
+        Ok(BatchCommitWriteStreamsResponse {
+            commit_time: Some(system_time_to_timestamp(SystemTime::now())),
+            stream_errors,
+        })
+    }
+}
+
+/// An `AppendRowsResponse` for rows appended at `offset`.
+fn append_rows_result(write_stream: &str, offset: Option<i64>) -> AppendRowsResponse {
+    AppendRowsResponse {
+        response: Some(append_rows_response::Response::AppendResult(
+            append_rows_response::AppendResult { offset },
+        )),
+        write_stream: write_stream.to_string(),
+        ..Default::default()
+    }
+}
+
+/// An `AppendRowsResponse` reporting `status`.
+fn append_rows_error(status: Status) -> AppendRowsResponse {
+    AppendRowsResponse {
+        response: Some(append_rows_response::Response::Error(RpcStatus {
+            code: status.code() as i32,
+            message: status.message().to_string(),
+            details: Vec::new(),
+        })),
+        ..Default::default()
+    }
+}
+
+type FakeReadRowsStream =
+    Pin<Box<dyn Stream<Item = Result<ReadRowsResponse, Status>> + Send + Sync + 'static>>;
+
+struct FakeBigQueryRead {
+    state: Arc<Mutex<State>>,
+}
+
+impl FakeBigQueryRead {
+    fn with_state<T>(&self, f: impl FnOnce(&mut State) -> T) -> T {
+        let mut state = self.state.lock().unwrap();
+        f(&mut state)
+    }
+}
+
+#[tonic::async_trait]
+impl BigQueryRead for FakeBigQueryRead {
+    async fn create_read_session(
+        &self,
+        request: Request<CreateReadSessionRequest>,
+    ) -> Result<Response<BigQueryReadSession>, Status> {
+        let req = request.into_inner();
+        self.with_state(|state| state.create_read_session(req))
+            .map(Response::new)
+    }
+
+    type ReadRowsStream = FakeReadRowsStream;
+
+    async fn read_rows(
+        &self,
+        request: Request<ReadRowsRequest>,
+    ) -> Result<Response<Self::ReadRowsStream>, Status> {
+        let req = request.into_inner();
+        let (responses, stalled) = self.with_state(|state| state.read_rows(req))?;
+        let responses = stream::iter(responses);
+        let read_rows_stream: FakeReadRowsStream = if stalled {
+            Box::pin(responses.chain(stream::pending()))
+        } else {
+            Box::pin(responses)
+        };
+        Ok(Response::new(read_rows_stream))
+    }
+
+    async fn split_read_stream(
+        &self,
+        request: Request<SplitReadStreamRequest>,
+    ) -> Result<Response<SplitReadStreamResponse>, Status> {
+        let req = request.into_inner();
+        self.with_state(|state| state.split_read_stream(req))
+            .map(Response::new)
+    }
+}
+
+type FakeAppendRowsStream =
+    Pin<Box<dyn Stream<Item = Result<AppendRowsResponse, Status>> + Send + Sync + 'static>>;
+
+struct FakeBigQueryWrite {
+    state: Arc<Mutex<State>>,
+}
+
+impl FakeBigQueryWrite {
+    fn with_state<T>(&self, f: impl FnOnce(&mut State) -> T) -> T {
+        let mut state = self.state.lock().unwrap();
+        f(&mut state)
+    }
+}
+
+#[tonic::async_trait]
+impl BigQueryWrite for FakeBigQueryWrite {
+    async fn create_write_stream(
+        &self,
+        request: Request<CreateWriteStreamRequest>,
+    ) -> Result<Response<WriteStream>, Status> {
+        let req = request.into_inner();
+        self.with_state(|state| state.create_write_stream(req))
+            .map(Response::new)
+    }
+
+    type AppendRowsStream = FakeAppendRowsStream;
+
+    async fn append_rows(
+        &self,
+        request: Request<Streaming<AppendRowsRequest>>,
+    ) -> Result<Response<Self::AppendRowsStream>, Status> {
+        let mut requests = request.into_inner();
+        let state = self.state.clone();
+        let (responses, receiver) = mpsc::unbounded();
+
+        // The requests stream cannot be shared between threads, so it is consumed
+        // in a task of its own
+        tokio::spawn(async move {
+            let mut write_stream: Option<String> = None;
+            while let Some(req) = requests.next().await {
+                let response = match req {
+                    Ok(req) => {
+                        let first = write_stream.is_none();
+                        let write_stream =
+                            write_stream.get_or_insert_with(|| req.write_stream.clone());
+                        let mut state = state.lock().unwrap();
+                        Ok(state
+                            .append_rows(write_stream, first, req)
+                            .unwrap_or_else(append_rows_error))
+                    }
+                    Err(status) => Err(status),
+                };
+                let failed = response.is_err();
+                if responses.unbounded_send(response).is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        let append_rows_stream: FakeAppendRowsStream = Box::pin(receiver);
+        Ok(Response::new(append_rows_stream))
+    }
+
+    async fn get_write_stream(
+        &self,
+        _request: Request<GetWriteStreamRequest>,
+    ) -> Result<Response<WriteStream>, Status> {
+        Err(Status::unimplemented("GetWriteStream"))
+    }
+
+    async fn finalize_write_stream(
+        &self,
+        request: Request<FinalizeWriteStreamRequest>,
+    ) -> Result<Response<FinalizeWriteStreamResponse>, Status> {
+        let req = request.into_inner();
+        self.with_state(|state| state.finalize_write_stream(req))
+            .map(Response::new)
+    }
+
+    async fn batch_commit_write_streams(
+        &self,
+        request: Request<BatchCommitWriteStreamsRequest>,
+    ) -> Result<Response<BatchCommitWriteStreamsResponse>, Status> {
+        let req = request.into_inner();
+        self.with_state(|state| state.batch_commit_write_streams(req))
+            .map(Response::new)
+    }
+
+    async fn flush_rows(
+        &self,
+        request: Request<FlushRowsRequest>,
+    ) -> Result<Response<FlushRowsResponse>, Status> {
+        let req = request.into_inner();
+        self.with_state(|state| state.flush_rows(req))
+            .map(Response::new)
+    }
+}
+
+/// Serialize `batches` as an Arrow IPC stream, with the continuation markers that the
+/// API prefixes its messages with.
+#[cfg(feature = "arrow")]
+fn arrow_ipc_stream(schema: &ArrowSchemaDef, batches: &[RecordBatch]) -> Result<Vec<u8>, Error> {
+    let options = IpcWriteOptions::try_new(8, false, MetadataVersion::V4)?;
+    let mut buf = Vec::new();
+    {
+        let mut writer = StreamWriter::try_new_with_options(&mut buf, schema, options)?;
+        for batch in batches {
+            writer.write(batch)?;
+        }
+        writer.finish()?;
+    }
+    Ok(buf)
+}
+
+/// The length of the end-of-stream marker of an Arrow IPC stream, which depends
+/// on whether it is in the legacy format.
+#[cfg(feature = "arrow")]
+fn eos_len(ipc_stream: &[u8]) -> usize {
+    if ipc_stream.ends_with(&[255, 255, 255, 255, 0, 0, 0, 0]) {
+        8
+    } else {
+        4
+    }
+}
+
+/// A `RESOURCE_EXHAUSTED` status, with a `RetryInfo` asking to retry after `retry_delay`.
+fn quota_exceeded(retry_delay: Duration) -> Status {
+    let retry_info = RetryInfo {
+        retry_delay: Some(prost_types::Duration {
+            seconds: retry_delay.as_secs() as i64,
+            nanos: retry_delay.subsec_nanos() as i32,
+        }),
+    };
+    let mut value = Vec::new();
+    retry_info.encode(&mut value).unwrap();
+    let message = "quota exceeded for CreateReadSession requests";
+    let rpc_status = RpcStatus {
+        code: Code::ResourceExhausted as i32,
+        message: message.to_string(),
+        details: vec![Any {
+            type_url: "type.googleapis.com/google.rpc.RetryInfo".to_string(),
+            value,
+        }],
+    };
+    let mut details = Vec::new();
+    rpc_status.encode(&mut details).unwrap();
+    Status::with_details(Code::ResourceExhausted, message, Bytes::from(details))
+}
+
+/// A builder for [`FakeServer`](FakeServer).
+#[derive(Default)]
+pub struct FakeServerBuilder {
+    tables: HashMap<String, FakeTable>,
+    unavailable_after: Option<usize>,
+    stall_after: Option<usize>,
+    expired_sessions: usize,
+    exhausted_quota: Option<(usize, Duration)>,
+    finalize_quota: Option<usize>,
+}
+
+impl FakeServerBuilder {
+    /// Serve `table` in the Arrow format, with the given `schema`. Each of `batches`
+    /// is sent as a single `ReadRowsResponse`.
+    #[cfg(feature = "arrow")]
+    pub fn arrow_table(
+        mut self,
+        table: Table,
+        schema: SchemaRef,
+        batches: Vec<RecordBatch>,
+    ) -> Result<Self, Error> {
+        let schema_only = arrow_ipc_stream(&schema, &[])?;
+        let eos = eos_len(&schema_only);
+        let serialized_schema = schema_only[..schema_only.len() - eos].to_vec();
+
+        let mut messages = Vec::with_capacity(batches.len());
+        for batch in batches.iter() {
+            let ipc_stream = arrow_ipc_stream(&schema, std::slice::from_ref(batch))?;
+            let serialized_record_batch =
+                ipc_stream[serialized_schema.len()..ipc_stream.len() - eos].to_vec();
+            let row_count = batch.num_rows() as i64;
+            messages.push(FakeMessage {
+                rows: Rows::ArrowRecordBatch(ArrowRecordBatch {
+                    serialized_record_batch,
+                    row_count,
+                }),
+                row_count,
+            });
+        }
+
+        let fake_table = FakeTable {
+            schema: SerializedSchema::Arrow(serialized_schema),
+            messages,
+        };
+        self.tables.insert(table.to_string(), fake_table);
+        Ok(self)
+    }
+
+    /// Serve `table` in the Avro format, with the given JSON `schema`. Each of `messages`
+    /// is a list of rows, sent as a single `ReadRowsResponse`.
+    #[cfg(feature = "avro")]
+    pub fn avro_table(
+        mut self,
+        table: Table,
+        schema: &str,
+        messages: Vec<Vec<AvroValue>>,
+    ) -> Result<Self, Error> {
+        let parsed_schema = avro_rs::Schema::parse_str(schema)?;
+
+        let mut fake_messages = Vec::with_capacity(messages.len());
+        for rows in messages {
+            let mut serialized_binary_rows = Vec::new();
+            for row in rows.iter() {
+                serialized_binary_rows.extend(avro_rs::to_avro_datum(&parsed_schema, row.clone())?);
+            }
+            let row_count = rows.len() as i64;
+            fake_messages.push(FakeMessage {
+                rows: Rows::AvroRows(AvroRows {
+                    serialized_binary_rows,
+                    row_count,
+                }),
+                row_count,
+            });
+        }
+
+        let fake_table = FakeTable {
+            schema: SerializedSchema::Avro(schema.to_string()),
+            messages: fake_messages,
+        };
+        self.tables.insert(table.to_string(), fake_table);
+        Ok(self)
+    }
+
+    /// Make each stream fail once with `UNAVAILABLE` after sending `messages` responses,
+    /// to exercise the recovery of readers.
+    pub fn unavailable_after(mut self, messages: usize) -> Self {
+        self.unavailable_after = Some(messages);
+        self
+    }
+
+    /// Make each stream stop sending responses, without ending, after sending `messages`
+    /// responses, to exercise timeouts and cancellation.
+    pub fn stall_after(mut self, messages: usize) -> Self {
+        self.stall_after = Some(messages);
+        self
+    }
+
+    /// Make the first `sessions` read sessions created expire immediately: reading their
+    /// streams fails with `FAILED_PRECONDITION`, like the actual API after 6 hours.
+    pub fn expire_sessions(mut self, sessions: usize) -> Self {
+        self.expired_sessions = sessions;
+        self
+    }
+
+    /// Make the first `calls` to `CreateReadSession` fail with `RESOURCE_EXHAUSTED`,
+    /// asking to retry after `retry_delay`.
+    pub fn exhaust_quota(mut self, calls: usize, retry_delay: Duration) -> Self {
+        self.exhausted_quota = Some((calls, retry_delay));
+        self
+    }
+
+    /// Make the calls to `FinalizeWriteStream` after the first `calls` fail with
+    /// `INTERNAL`.
+    pub fn fail_finalize_after(mut self, calls: usize) -> Self {
+        self.finalize_quota = Some(calls);
+        self
+    }
+
+    /// Start serving on a local port, in a task spawned on the current Tokio runtime.
+    pub async fn start(self) -> Result<FakeServer, Error> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+
+        let state = State {
+            tables: self.tables,
+            unavailable_after: self.unavailable_after,
+            stall_after: self.stall_after,
+            expired_sessions: self.expired_sessions,
+            exhausted_quota: self.exhausted_quota,
+            finalize_quota: self.finalize_quota,
+            ..Default::default()
+        };
+        let state = Arc::new(Mutex::new(state));
+        let read_service = FakeBigQueryRead {
+            state: state.clone(),
+        };
+        let write_service = FakeBigQueryWrite {
+            state: state.clone(),
+        };
+
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let server = Server::builder()
+            .add_service(BigQueryReadServer::new(read_service))
+            .add_service(BigQueryWriteServer::new(write_service))
+            .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async move {
+                let _ = signal.await;
+            });
+        tokio::spawn(server);
+
+        Ok(FakeServer {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+        })
+    }
+}
+
+/// An in-process fake BigQuery Storage API server. It shuts down when dropped.
+pub struct FakeServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl FakeServer {
+    /// Create a new [`FakeServerBuilder`](FakeServerBuilder).
+    pub fn builder() -> FakeServerBuilder {
+        FakeServerBuilder::default()
+    }
+
+    /// The local address the server listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The read options of the read sessions created so far, in order. The fake server
+    /// records them, but always reads all the fields and rows of its tables.
+    pub fn read_options(&self) -> Vec<TableReadOptions> {
+        self.state.lock().unwrap().read_options.clone()
+    }
+
+    /// Make the read sessions created so far expire now: reading their streams fails
+    /// with `FAILED_PRECONDITION`, including the streams being read when they resume.
+    pub fn expire_sessions_now(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.expired_sessions = state.num_sessions;
+    }
+
+    /// The rows written to `table` through the Write API, as they were serialized.
+    pub fn written_rows(&self, table: &Table) -> Vec<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        state
+            .written
+            .get(&table.to_string())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Create a [`Client`](crate::client::Client) connected to this server, without
+    /// TLS and without authentication.
+    pub async fn client(&self) -> Result<Client, Error> {
+        let channel = Endpoint::new(format!("http://{}", self.addr))?
+            .connect()
+            .await?;
+        Ok(Client::from_channel(None, channel))
+    }
+}
+
+impl Drop for FakeServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
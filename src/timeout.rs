@@ -0,0 +1,101 @@
+//! Bounding how long calls to the BigQuery Storage API may take.
+use futures::future::{self, Either};
+use futures::pin_mut;
+use tokio_util::sync::CancellationToken;
+use tonic::metadata::MetadataValue;
+use tonic::{Request, Status};
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::Error;
+
+/// The limits of the calls made by a [`Client`](crate::client::Client).
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CallLimits {
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) idle_timeout: Option<Duration>,
+    pub(crate) cancellation: Option<CancellationToken>,
+}
+
+impl CallLimits {
+    /// The deadline of a call starting now.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.timeout.map(|timeout| Instant::now() + timeout)
+    }
+
+    /// Run `call` to completion, unless `deadline` passes first, in which case
+    /// [`Error::Timeout`](crate::Error::Timeout) is returned, or the call is cancelled,
+    /// in which case the error has the `CANCELLED` status.
+    pub(crate) async fn run<F: Future>(
+        &self,
+        deadline: Option<Instant>,
+        call: F,
+    ) -> Result<F::Output, Error> {
+        let bounded = async move {
+            match deadline {
+                Some(deadline) => tokio::time::timeout_at(deadline.into(), call)
+                    .await
+                    .map_err(Error::from),
+                None => Ok(call.await),
+            }
+        };
+        let cancelled = match &self.cancellation {
+            Some(cancellation) => cancellation.cancelled(),
+            None => return bounded.await,
+        };
+        pin_mut!(bounded, cancelled);
+        match future::select(bounded, cancelled).await {
+            Either::Left((output, _)) => output,
+            Either::Right(_) => Err(Status::cancelled("the call was cancelled").into()),
+        }
+    }
+}
+
+/// Tell the server not to work on `req` past `deadline`, with the `grpc-timeout` header.
+pub(crate) fn set_deadline<T>(
+    req: &mut Request<T>,
+    deadline: Option<Instant>,
+) -> Result<(), Error> {
+    if let Some(deadline) = deadline {
+        let timeout = grpc_timeout(deadline.saturating_duration_since(Instant::now()));
+        req.metadata_mut()
+            .insert("grpc-timeout", MetadataValue::from_str(&timeout)?);
+    }
+    Ok(())
+}
+
+/// Format `timeout` as the value of a `grpc-timeout` header: at most 8 digits, in the
+/// most precise unit that fits.
+fn grpc_timeout(timeout: Duration) -> String {
+    const UNITS: [(&str, u128); 6] = [
+        ("n", 1),
+        ("u", 1_000),
+        ("m", 1_000_000),
+        ("S", 1_000_000_000),
+        ("M", 60_000_000_000),
+        ("H", 3_600_000_000_000),
+    ];
+    let nanos = timeout.as_nanos();
+    for (unit, unit_nanos) in UNITS.iter() {
+        let value = nanos / unit_nanos;
+        if value < 100_000_000 {
+            return format!("{}{}", value, unit);
+        }
+    }
+    "99999999H".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_grpc_timeouts() {
+        assert_eq!(grpc_timeout(Duration::from_nanos(1500)), "1500n");
+        assert_eq!(grpc_timeout(Duration::from_millis(250)), "250000u");
+        assert_eq!(grpc_timeout(Duration::from_secs(30)), "30000000u");
+        assert_eq!(grpc_timeout(Duration::from_secs(6 * 3600)), "21600000m");
+        assert_eq!(grpc_timeout(Duration::from_secs(u64::MAX)), "99999999H");
+    }
+}
@@ -0,0 +1,43 @@
+//! The spans emitted with the `tracing` feature. Without it, spans are no-ops that
+//! compile to nothing, so they can be created unconditionally.
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::{Instrument, Span};
+
+/// Create a span at `level`, with the syntax of [`tracing::span!`], e.g.
+/// `span!(DEBUG, "read_rows", stream = %name)`.
+#[cfg(feature = "tracing")]
+macro_rules! span {
+    ($level:ident, $($args:tt)*) => {
+        tracing::span!(tracing::Level::$level, $($args)*)
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! span {
+    ($($args:tt)*) => {
+        $crate::trace::Span
+    };
+}
+
+pub(crate) use span;
+
+#[cfg(not(feature = "tracing"))]
+#[derive(Clone, Debug)]
+pub(crate) struct Span;
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    pub(crate) fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        f()
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) trait Instrument: Sized {
+    fn instrument(self, _span: Span) -> Self {
+        self
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl<T> Instrument for T {}
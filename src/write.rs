@@ -0,0 +1,795 @@
+//! Writing rows into BigQuery tables with the [Storage Write API](https://cloud.google.com/bigquery/docs/write-api).
+//!
+//! Rows are sent as serialized protocol buffer messages, described by a
+//! [`ProtoSchema`](crate::googleapis::ProtoSchema) wrapping their `DescriptorProto`.
+//! They are either appended to the `_default` stream of a table with
+//! [`Client::append_rows`](crate::client::Client::append_rows), or to streams created in
+//! a [`WriteSession`](WriteSession), which can be committed atomically.
+use bytes::Bytes;
+use futures::channel::mpsc::UnboundedSender;
+use prost::Message;
+use tonic::{Code, Status, Streaming};
+
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::googleapis::google::rpc::Status as RpcStatus;
+use crate::googleapis::{
+    append_rows_request::{ProtoData, Rows},
+    append_rows_response::{AppendResult, Response},
+    write_stream::Type as WriteStreamType,
+    AppendRowsRequest, AppendRowsResponse, BatchCommitWriteStreamsRequest,
+    CreateWriteStreamRequest, FinalizeWriteStreamRequest, FlushRowsRequest, ProtoRows, ProtoSchema,
+    RowError, StorageError, TableSchema, WriteStream,
+};
+use crate::handle::timestamp_to_system_time;
+use crate::{Client, Error};
+
+#[cfg(feature = "arrow")]
+use crate::row::downcast;
+#[cfg(feature = "arrow")]
+use arrow::array::{
+    Array, ArrayRef, BinaryArray, BooleanArray, Date32Array, Date64Array, Float32Array,
+    Float64Array, Int16Array, Int32Array, Int64Array, Int8Array, LargeBinaryArray, LargeListArray,
+    LargeStringArray, ListArray, StringArray, StructArray, TimestampMicrosecondArray,
+    TimestampMillisecondArray, TimestampNanosecondArray, TimestampSecondArray, UInt16Array,
+    UInt32Array, UInt8Array,
+};
+#[cfg(feature = "arrow")]
+use arrow::datatypes::{DataType, Field, Schema as ArrowSchemaDef, TimeUnit};
+#[cfg(feature = "arrow")]
+use arrow::error::ArrowError;
+#[cfg(feature = "arrow")]
+use arrow::record_batch::RecordBatch;
+#[cfg(feature = "arrow")]
+use prost::encoding;
+#[cfg(feature = "arrow")]
+use prost_types::field_descriptor_proto::{Label, Type};
+#[cfg(feature = "arrow")]
+use prost_types::{DescriptorProto, FieldDescriptorProto};
+
+/// The outcome of a successful append.
+#[derive(Clone, Debug)]
+pub struct AppendedRows {
+    offset: Option<i64>,
+    updated_schema: Option<TableSchema>,
+}
+
+impl AppendedRows {
+    /// The offset in the write stream at which the rows were appended. This is
+    /// `None` for the `_default` stream, which does not track offsets.
+    pub fn offset(&self) -> Option<i64> {
+        self.offset
+    }
+
+    /// The new schema of the table, if it changed since the writer was created.
+    /// Rows using the new columns can only be appended by a new writer.
+    pub fn updated_schema(&self) -> Option<&TableSchema> {
+        self.updated_schema.as_ref()
+    }
+}
+
+/// An append rejected by the API. None of its rows were written.
+#[derive(Debug)]
+pub struct AppendError {
+    status: Status,
+    row_errors: Vec<RowError>,
+}
+
+impl AppendError {
+    /// The status the append was rejected with.
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
+
+    /// The rows that caused the append to be rejected, if the API could tell, by
+    /// their index in the append.
+    pub fn row_errors(&self) -> &[RowError] {
+        &self.row_errors
+    }
+}
+
+impl std::fmt::Display for AppendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.status)?;
+        for row_error in self.row_errors.iter() {
+            write!(f, "; row {}: {}", row_error.index, row_error.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AppendError {}
+
+/// A commit of a [`WriteSession`](WriteSession) rejected by the API. None of its streams
+/// were committed.
+#[derive(Debug)]
+pub struct CommitError {
+    stream_errors: Vec<StorageError>,
+}
+
+impl CommitError {
+    /// The reasons the streams could not be committed.
+    pub fn stream_errors(&self) -> &[StorageError] {
+        &self.stream_errors
+    }
+}
+
+impl std::fmt::Display for CommitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "streams were not committed")?;
+        for stream_error in self.stream_errors.iter() {
+            write!(
+                f,
+                "; {}: {}",
+                stream_error.entity, stream_error.error_message
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CommitError {}
+
+/// The failure to finalize a stream of a [`WriteSession`](WriteSession). The streams
+/// finalized before it stay finalized, and are skipped when finalizing again.
+#[derive(Debug)]
+pub struct FinalizeError {
+    stream: String,
+    error: Box<Error>,
+}
+
+impl FinalizeError {
+    /// The name of the stream that was not finalized.
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
+
+    /// The reason the stream was not finalized.
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+}
+
+impl std::fmt::Display for FinalizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "stream {} was not finalized: {}",
+            self.stream, self.error
+        )
+    }
+}
+
+impl std::error::Error for FinalizeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.error)
+    }
+}
+
+/// A stream created in a [`WriteSession`](WriteSession), with its row count once
+/// finalized.
+struct SessionStream {
+    name: String,
+    row_count: Option<i64>,
+}
+
+type SessionStreams = Arc<Mutex<Vec<SessionStream>>>;
+
+fn record_finalized(streams: &SessionStreams, name: &str, row_count: i64) {
+    let mut streams = streams.lock().unwrap();
+    if let Some(stream) = streams.iter_mut().find(|stream| stream.name == name) {
+        stream.row_count = Some(row_count);
+    }
+}
+
+/// A connection to a write stream over a bidirectional `AppendRows` call.
+pub(crate) struct AppendRowsConnection {
+    pub(crate) requests: UnboundedSender<AppendRowsRequest>,
+    pub(crate) responses: Streaming<AppendRowsResponse>,
+}
+
+/// Appends rows to a single write stream. Create it with
+/// [`Client::default_stream_writer`](crate::client::Client::default_stream_writer) or
+/// [`WriteSession::create_stream`](WriteSession::create_stream).
+///
+/// The underlying `AppendRows` call is opened on the first append and kept open for
+/// the following ones. If it fails, it is opened again on the next append.
+pub struct StreamWriter {
+    client: Client,
+    write_stream: String,
+    schema: ProtoSchema,
+    connection: Option<AppendRowsConnection>,
+    /// The streams of the session this stream was created in, if any.
+    session: Option<SessionStreams>,
+}
+
+impl StreamWriter {
+    pub(crate) fn new(client: Client, write_stream: String, schema: ProtoSchema) -> Self {
+        Self {
+            client,
+            write_stream,
+            schema,
+            connection: None,
+            session: None,
+        }
+    }
+
+    /// The name of the write stream, `projects/{}/datasets/{}/tables/{}/streams/{}`.
+    pub fn write_stream(&self) -> &str {
+        &self.write_stream
+    }
+
+    /// Append `rows`, each a protocol buffer message serialized according to the
+    /// schema of this writer, and wait for the API to acknowledge them.
+    pub async fn append(&mut self, rows: Vec<Vec<u8>>) -> Result<AppendedRows, Error> {
+        self.send(rows, None).await
+    }
+
+    /// Append `rows` at `offset`, which must be the number of rows already appended to
+    /// the stream. This is not supported by the `_default` stream.
+    ///
+    /// Retrying an append at the same offset is idempotent: if the rows were already
+    /// written, the retry fails with an [`AppendError`](AppendError) whose status is
+    /// `ALREADY_EXISTS`, of kind [`ErrorKind::AlreadyExists`](crate::ErrorKind::AlreadyExists),
+    /// and the rows are not written twice.
+    pub async fn append_at(
+        &mut self,
+        offset: i64,
+        rows: Vec<Vec<u8>>,
+    ) -> Result<AppendedRows, Error> {
+        self.send(rows, Some(offset)).await
+    }
+
+    /// Finalize the stream, so that no more rows can be appended to it. This is
+    /// required before committing a `PENDING` stream. Returns the number of rows in the stream.
+    pub async fn finalize(&mut self) -> Result<i64, Error> {
+        // Appends in flight would fail anyway
+        self.connection = None;
+        let req = FinalizeWriteStreamRequest {
+            name: self.write_stream.clone(),
+        };
+        let response = self.client.clone().finalize_write_stream(req).await?;
+        if let Some(streams) = &self.session {
+            record_finalized(streams, &self.write_stream, response.row_count);
+        }
+        Ok(response.row_count)
+    }
+
+    /// Make the rows of a `BUFFERED` stream up to `offset` included visible to readers.
+    /// Returns the offset the stream was flushed to.
+    pub async fn flush(&self, offset: i64) -> Result<i64, Error> {
+        let req = FlushRowsRequest {
+            write_stream: self.write_stream.clone(),
+            offset: Some(offset),
+        };
+        let response = self.client.clone().flush_rows(req).await?;
+        Ok(response.offset)
+    }
+
+    /// Append the rows of `batch`, see [`encode_record_batch`](encode_record_batch). The
+    /// schema of this writer must be built from the schema of `batch`, with
+    /// [`proto_schema_from_arrow`](proto_schema_from_arrow).
+    #[cfg(feature = "arrow")]
+    pub async fn append_record_batch(
+        &mut self,
+        batch: &RecordBatch,
+    ) -> Result<AppendedRows, Error> {
+        let rows = encode_record_batch(batch)?;
+        self.send(rows, None).await
+    }
+
+    /// Append the rows of `batch` at `offset`, see [`append_at`](StreamWriter::append_at).
+    #[cfg(feature = "arrow")]
+    pub async fn append_record_batch_at(
+        &mut self,
+        offset: i64,
+        batch: &RecordBatch,
+    ) -> Result<AppendedRows, Error> {
+        let rows = encode_record_batch(batch)?;
+        self.send(rows, Some(offset)).await
+    }
+
+    async fn send(
+        &mut self,
+        rows: Vec<Vec<u8>>,
+        offset: Option<i64>,
+    ) -> Result<AppendedRows, Error> {
+        let result = self.try_send(rows, offset).await;
+        match result {
+            // Rejected appends are reported in band, the call itself is still usable
+            Err(Error::Append(_)) | Ok(_) => {}
+            Err(_) => self.connection = None,
+        }
+        result
+    }
+
+    async fn try_send(
+        &mut self,
+        rows: Vec<Vec<u8>>,
+        offset: Option<i64>,
+    ) -> Result<AppendedRows, Error> {
+        let mut proto_data = ProtoData {
+            writer_schema: None,
+            rows: Some(ProtoRows {
+                serialized_rows: rows,
+            }),
+        };
+        let mut req = AppendRowsRequest {
+            offset,
+            ..Default::default()
+        };
+
+        let connection = match self.connection.take() {
+            Some(connection) => {
+                req.rows = Some(Rows::ProtoRows(proto_data));
+                connection
+                    .requests
+                    .unbounded_send(req)
+                    .map_err(|_| Error::invalid("AppendRows call closed"))?;
+                connection
+            }
+            None => {
+                // The first request of a call names the stream and carries the schema
+                proto_data.writer_schema = Some(self.schema.clone());
+                req.write_stream = self.write_stream.clone();
+                req.rows = Some(Rows::ProtoRows(proto_data));
+                self.client.clone().append_rows_connection(req).await?
+            }
+        };
+        let connection = self.connection.get_or_insert(connection);
+
+        let response = connection
+            .responses
+            .message()
+            .await?
+            .ok_or_else(|| Error::invalid("AppendRows call ended without a response"))?;
+        appended_rows(response)
+    }
+}
+
+fn appended_rows(response: AppendRowsResponse) -> Result<AppendedRows, Error> {
+    let AppendRowsResponse {
+        response,
+        updated_schema,
+        row_errors,
+        ..
+    } = response;
+    match response {
+        Some(Response::AppendResult(AppendResult { offset })) => Ok(AppendedRows {
+            offset,
+            updated_schema,
+        }),
+        Some(Response::Error(status)) => Err(Error::Append(AppendError {
+            status: from_rpc_status(status),
+            row_errors,
+        })),
+        None => Err(Error::invalid("empty AppendRows response")),
+    }
+}
+
+/// Convert a status returned in a message body into a [`tonic::Status`], keeping the
+/// encoded status as its details like gRPC does for the status of a call.
+pub(crate) fn from_rpc_status(status: RpcStatus) -> Status {
+    let mut details = Vec::with_capacity(status.encoded_len());
+    let code = Code::from_i32(status.code);
+    let message = status.message.clone();
+    match status.encode(&mut details) {
+        Ok(()) => Status::with_details(code, message, Bytes::from(details)),
+        Err(_) => Status::new(code, message),
+    }
+}
+
+/// A set of write streams created on a table, mirroring a [`ReadSession`](crate::client::ReadSession).
+/// Create it with [`Client::write_session`](crate::client::Client::write_session).
+///
+/// The type of the streams decides when their rows become visible: immediately for
+/// `COMMITTED` streams, when flushed for `BUFFERED` streams and, for `PENDING` streams,
+/// when the session is [committed](WriteSession::commit). Committing is atomic: all the
+/// rows of all the streams of the session are written, or none is.
+///
+/// Clones share the same set of streams.
+#[derive(Clone)]
+pub struct WriteSession {
+    client: Client,
+    table: String,
+    stream_type: WriteStreamType,
+    streams: SessionStreams,
+}
+
+impl WriteSession {
+    pub(crate) fn new(client: Client, table: String, stream_type: WriteStreamType) -> Self {
+        Self {
+            client,
+            table,
+            stream_type,
+            streams: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// The type of the streams of this session.
+    pub fn stream_type(&self) -> WriteStreamType {
+        self.stream_type
+    }
+
+    /// The names of the streams created in this session so far.
+    pub fn streams(&self) -> Vec<String> {
+        let streams = self.streams.lock().unwrap();
+        streams.iter().map(|stream| stream.name.clone()).collect()
+    }
+
+    /// Create a new stream in this session, and a [`StreamWriter`](StreamWriter) appending
+    /// rows described by `schema` to it.
+    pub async fn create_stream(&self, schema: ProtoSchema) -> Result<StreamWriter, Error> {
+        let mut write_stream = WriteStream::default();
+        write_stream.set_type(self.stream_type);
+        let req = CreateWriteStreamRequest {
+            parent: self.table.clone(),
+            write_stream: Some(write_stream),
+        };
+        let write_stream = self.client.clone().create_write_stream(req).await?;
+
+        self.streams.lock().unwrap().push(SessionStream {
+            name: write_stream.name.clone(),
+            row_count: None,
+        });
+        let mut writer = StreamWriter::new(self.client.clone(), write_stream.name, schema);
+        writer.session = Some(self.streams.clone());
+        Ok(writer)
+    }
+
+    /// Finalize all the streams of this session, skipping those already finalized by
+    /// their [`StreamWriter`](StreamWriter). Returns the total number of rows written to
+    /// them.
+    ///
+    /// If a stream cannot be finalized, fails with a [`FinalizeError`](FinalizeError)
+    /// naming it.
+    pub async fn finalize(&self) -> Result<i64, Error> {
+        let mut row_count = 0;
+        for name in self.streams() {
+            let finalized = self
+                .streams
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|stream| stream.name == name)
+                .and_then(|stream| stream.row_count);
+            if let Some(finalized) = finalized {
+                row_count += finalized;
+                continue;
+            }
+
+            let req = FinalizeWriteStreamRequest { name: name.clone() };
+            match self.client.clone().finalize_write_stream(req).await {
+                Ok(response) => {
+                    record_finalized(&self.streams, &name, response.row_count);
+                    row_count += response.row_count;
+                }
+                Err(error) => {
+                    return Err(Error::Finalize(FinalizeError {
+                        stream: name,
+                        error: Box::new(error),
+                    }))
+                }
+            }
+        }
+        Ok(row_count)
+    }
+
+    /// [Finalize](WriteSession::finalize) and commit all the streams of this session,
+    /// which must be `PENDING` streams. Returns the time at which the rows became visible.
+    pub async fn commit(&self) -> Result<SystemTime, Error> {
+        if self.stream_type != WriteStreamType::Pending {
+            return Err(Error::usage("only PENDING streams can be committed"));
+        }
+        self.finalize().await?;
+
+        let req = BatchCommitWriteStreamsRequest {
+            parent: self.table.clone(),
+            write_streams: self.streams(),
+        };
+        let response = self.client.clone().batch_commit_write_streams(req).await?;
+        match response.commit_time {
+            Some(commit_time) => timestamp_to_system_time(&commit_time),
+            None => Err(Error::Commit(CommitError {
+                stream_errors: response.stream_errors,
+            })),
+        }
+    }
+}
+
+/// Build the [`ProtoSchema`](crate::googleapis::ProtoSchema) of rows encoded from record
+/// batches of `schema` by [`encode_record_batch`](encode_record_batch).
+///
+/// Each column becomes an optional field numbered after its position, lists become
+/// repeated fields and structs nested messages. Dates are sent as days since the epoch,
+/// and timestamps as microseconds since the epoch. Other temporal types, decimals,
+/// dictionaries, unsigned 64-bit integers and nested lists are not supported.
+#[cfg(feature = "arrow")]
+pub fn proto_schema_from_arrow(schema: &ArrowSchemaDef) -> Result<ProtoSchema, Error> {
+    Ok(ProtoSchema {
+        proto_descriptor: Some(descriptor("Row", schema.fields())?),
+    })
+}
+
+/// Encode each row of `batch` as a protocol buffer message described by
+/// [`proto_schema_from_arrow`](proto_schema_from_arrow). Null values are left out of
+/// the messages; null items in lists are not supported.
+#[cfg(feature = "arrow")]
+pub fn encode_record_batch(batch: &RecordBatch) -> Result<Vec<Vec<u8>>, Error> {
+    (0..batch.num_rows())
+        .map(|row| encode_message(batch.columns().iter(), row))
+        .collect()
+}
+
+#[cfg(feature = "arrow")]
+fn unsupported(data_type: &DataType) -> Error {
+    Error::Arrow(ArrowError::SchemaError(format!(
+        "{:?} cannot be written to BigQuery",
+        data_type
+    )))
+}
+
+#[cfg(feature = "arrow")]
+fn descriptor(name: &str, fields: &[Field]) -> Result<DescriptorProto, Error> {
+    let mut descriptor = DescriptorProto {
+        name: Some(name.to_string()),
+        ..Default::default()
+    };
+
+    for (i, field) in fields.iter().enumerate() {
+        let (data_type, label) = match field.data_type() {
+            DataType::List(item) | DataType::LargeList(item) => (item.data_type(), Label::Repeated),
+            data_type => (data_type, Label::Optional),
+        };
+
+        let mut field_descriptor = FieldDescriptorProto {
+            name: Some(field.name().clone()),
+            number: Some(i as i32 + 1),
+            ..Default::default()
+        };
+        field_descriptor.set_label(label);
+
+        if let DataType::Struct(nested_fields) = data_type {
+            // Nested messages are declared in the message using them, to keep the
+            // descriptor self-contained
+            let type_name = format!("{}_Struct", field.name());
+            descriptor
+                .nested_type
+                .push(self::descriptor(&type_name, nested_fields)?);
+            field_descriptor.set_type(Type::Message);
+            field_descriptor.type_name = Some(type_name);
+        } else {
+            field_descriptor.set_type(scalar_type(data_type)?);
+        }
+        descriptor.field.push(field_descriptor);
+    }
+
+    Ok(descriptor)
+}
+
+#[cfg(feature = "arrow")]
+fn scalar_type(data_type: &DataType) -> Result<Type, Error> {
+    let proto_type = match data_type {
+        DataType::Boolean => Type::Bool,
+        DataType::Int8 | DataType::Int16 | DataType::Int32 => Type::Int32,
+        DataType::UInt8 | DataType::UInt16 => Type::Int32,
+        DataType::Int64 | DataType::UInt32 => Type::Int64,
+        DataType::Float32 => Type::Float,
+        DataType::Float64 => Type::Double,
+        DataType::Utf8 | DataType::LargeUtf8 => Type::String,
+        DataType::Binary | DataType::LargeBinary => Type::Bytes,
+        DataType::Date32(_) | DataType::Date64(_) => Type::Int32,
+        DataType::Timestamp(_, _) => Type::Int64,
+        other => return Err(unsupported(other)),
+    };
+    Ok(proto_type)
+}
+
+#[cfg(feature = "arrow")]
+fn encode_message<'a, I>(columns: I, row: usize) -> Result<Vec<u8>, Error>
+where
+    I: Iterator<Item = &'a ArrayRef>,
+{
+    let mut buf = Vec::new();
+    for (i, column) in columns.enumerate() {
+        encode_field(column.as_ref(), row, i as u32 + 1, &mut buf)?;
+    }
+    Ok(buf)
+}
+
+#[cfg(feature = "arrow")]
+fn encode_field(array: &dyn Array, row: usize, tag: u32, buf: &mut Vec<u8>) -> Result<(), Error> {
+    if array.is_null(row) {
+        return Ok(());
+    }
+
+    let items = match array.data_type() {
+        DataType::List(_) => downcast::<ListArray>(array)?.value(row),
+        DataType::LargeList(_) => downcast::<LargeListArray>(array)?.value(row),
+        _ => return encode_scalar(array, row, tag, buf),
+    };
+    for i in 0..items.len() {
+        if items.is_null(i) {
+            return Err(Error::Arrow(ArrowError::InvalidArgumentError(
+                "lists written to BigQuery cannot contain nulls".to_string(),
+            )));
+        }
+        encode_scalar(items.as_ref(), i, tag, buf)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "arrow")]
+fn encode_scalar(array: &dyn Array, row: usize, tag: u32, buf: &mut Vec<u8>) -> Result<(), Error> {
+    const MILLIS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+    match array.data_type() {
+        DataType::Boolean => {
+            encoding::bool::encode(tag, &downcast::<BooleanArray>(array)?.value(row), buf)
+        }
+        DataType::Int8 => {
+            let value = downcast::<Int8Array>(array)?.value(row) as i32;
+            encoding::int32::encode(tag, &value, buf)
+        }
+        DataType::Int16 => {
+            let value = downcast::<Int16Array>(array)?.value(row) as i32;
+            encoding::int32::encode(tag, &value, buf)
+        }
+        DataType::Int32 => {
+            encoding::int32::encode(tag, &downcast::<Int32Array>(array)?.value(row), buf)
+        }
+        DataType::UInt8 => {
+            let value = downcast::<UInt8Array>(array)?.value(row) as i32;
+            encoding::int32::encode(tag, &value, buf)
+        }
+        DataType::UInt16 => {
+            let value = downcast::<UInt16Array>(array)?.value(row) as i32;
+            encoding::int32::encode(tag, &value, buf)
+        }
+        DataType::Int64 => {
+            encoding::int64::encode(tag, &downcast::<Int64Array>(array)?.value(row), buf)
+        }
+        DataType::UInt32 => {
+            let value = downcast::<UInt32Array>(array)?.value(row) as i64;
+            encoding::int64::encode(tag, &value, buf)
+        }
+        DataType::Float32 => {
+            encoding::float::encode(tag, &downcast::<Float32Array>(array)?.value(row), buf)
+        }
+        DataType::Float64 => {
+            encoding::double::encode(tag, &downcast::<Float64Array>(array)?.value(row), buf)
+        }
+        DataType::Utf8 => {
+            let value = downcast::<StringArray>(array)?.value(row).to_string();
+            encoding::string::encode(tag, &value, buf)
+        }
+        DataType::LargeUtf8 => {
+            let value = downcast::<LargeStringArray>(array)?.value(row).to_string();
+            encoding::string::encode(tag, &value, buf)
+        }
+        DataType::Binary => {
+            let value = downcast::<BinaryArray>(array)?.value(row).to_vec();
+            encoding::bytes::encode(tag, &value, buf)
+        }
+        DataType::LargeBinary => {
+            let value = downcast::<LargeBinaryArray>(array)?.value(row).to_vec();
+            encoding::bytes::encode(tag, &value, buf)
+        }
+        DataType::Date32(_) => {
+            encoding::int32::encode(tag, &downcast::<Date32Array>(array)?.value(row), buf)
+        }
+        DataType::Date64(_) => {
+            let millis = downcast::<Date64Array>(array)?.value(row);
+            let days = millis.div_euclid(MILLIS_PER_DAY) as i32;
+            encoding::int32::encode(tag, &days, buf)
+        }
+        DataType::Timestamp(unit, _) => {
+            let micros = match unit {
+                TimeUnit::Second => downcast::<TimestampSecondArray>(array)?
+                    .value(row)
+                    .checked_mul(1_000_000),
+                TimeUnit::Millisecond => downcast::<TimestampMillisecondArray>(array)?
+                    .value(row)
+                    .checked_mul(1_000),
+                TimeUnit::Microsecond => {
+                    Some(downcast::<TimestampMicrosecondArray>(array)?.value(row))
+                }
+                TimeUnit::Nanosecond => Some(
+                    downcast::<TimestampNanosecondArray>(array)?
+                        .value(row)
+                        .div_euclid(1_000),
+                ),
+            };
+            let micros = micros.ok_or_else(|| {
+                Error::Arrow(ArrowError::InvalidArgumentError(format!(
+                    "timestamp of row {} is out of range",
+                    row
+                )))
+            })?;
+            encoding::int64::encode(tag, &micros, buf)
+        }
+        DataType::Struct(_) => {
+            let struct_array = downcast::<StructArray>(array)?;
+            let message = encode_message(struct_array.columns().into_iter(), row)?;
+            encoding::bytes::encode(tag, &message, buf)
+        }
+        other => return Err(unsupported(other)),
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "arrow"))]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    #[test]
+    fn proto_schema_of_nested_arrow_schema() {
+        let address = DataType::Struct(vec![
+            Field::new("city", DataType::Utf8, true),
+            Field::new("zip", DataType::Int32, true),
+        ]);
+        let schema = ArrowSchemaDef::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new(
+                "tags",
+                DataType::List(Box::new(Field::new("item", DataType::Utf8, false))),
+                true,
+            ),
+            Field::new("address", address, true),
+        ]);
+
+        let descriptor = proto_schema_from_arrow(&schema)
+            .unwrap()
+            .proto_descriptor
+            .unwrap();
+        let fields = &descriptor.field;
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields[0].r#type(), Type::Int64);
+        assert_eq!(fields[1].label(), Label::Repeated);
+        assert_eq!(fields[1].r#type(), Type::String);
+        assert_eq!(fields[2].r#type(), Type::Message);
+        assert_eq!(fields[2].type_name.as_deref(), Some("address_Struct"));
+        assert_eq!(descriptor.nested_type[0].field.len(), 2);
+
+        let unsupported = ArrowSchemaDef::new(vec![Field::new("n", DataType::UInt64, false)]);
+        assert!(proto_schema_from_arrow(&unsupported).is_err());
+    }
+
+    #[test]
+    fn encode_record_batch_rows() {
+        let schema = Arc::new(ArrowSchemaDef::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec![Some("a"), None])),
+            ],
+        )
+        .unwrap();
+
+        let rows = encode_record_batch(&batch).unwrap();
+        // Field 1 as a varint, field 2 as a length-delimited string
+        assert_eq!(rows[0], vec![8, 1, 18, 1, b'a']);
+        // The null name is left out
+        assert_eq!(rows[1], vec![8, 2]);
+    }
+
+    #[test]
+    fn reject_out_of_range_timestamps() {
+        let schema = Arc::new(ArrowSchemaDef::new(vec![Field::new(
+            "at",
+            DataType::Timestamp(TimeUnit::Second, None),
+            false,
+        )]));
+        let at: ArrayRef = Arc::new(TimestampSecondArray::from_vec(vec![i64::MAX], None));
+        let batch = RecordBatch::try_new(schema, vec![at]).unwrap();
+
+        assert!(encode_record_batch(&batch).is_err());
+    }
+}